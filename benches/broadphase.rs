@@ -0,0 +1,87 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use ggez_test::{
+    aabb::{CollisionSystem, AABB},
+    component::ComponentManager,
+    entity::EntitySystem,
+    events::Events,
+    resource::Resources,
+    rigidbody::Rigidbody,
+    transform::Transform,
+    vector2::Vector2,
+};
+
+const BLOCK: f32 = 32.0;
+const BODIES: usize = 200;
+const STEP: f32 = 1.0 / 60.0;
+
+// a square field of static blocks with a gap next to every column, and a couple
+// hundred bodies falling down the gaps. only the bodies move, so the cost of a
+// step should barely depend on how many blocks there are
+fn world(blocks: usize) -> (EntitySystem, ComponentManager, f32) {
+    let mut entity_system = EntitySystem::new();
+    let mut component_manager = ComponentManager::new();
+
+    let side = (blocks as f32).sqrt().ceil() as usize;
+    for i in 0..blocks {
+        let pos = Vector2::new((i % side) as f32 * BLOCK * 2.0, (i / side) as f32 * BLOCK * 2.0);
+        entity_system.spawn((
+            Transform::new(pos),
+            AABB { w: BLOCK, h: BLOCK, ..AABB::default() },
+        ), &mut component_manager);
+    }
+
+    for i in 0..BODIES {
+        let pos = Vector2::new((i % side) as f32 * BLOCK * 2.0 + BLOCK, -((i / side) as f32 + 1.0) * BLOCK);
+        entity_system.spawn((
+            Transform::new(pos),
+            Rigidbody { vel: Vector2::new(0.0, 600.0), ..Rigidbody::default() },
+            AABB { w: BLOCK * 0.5, h: BLOCK * 0.5, ..AABB::default() },
+        ), &mut component_manager);
+    }
+
+    (entity_system, component_manager, side as f32 * BLOCK * 2.0)
+}
+
+// what `PhysicsSystem` would do, minus gravity: bodies fall through the field
+// and start over at the top once they've left it
+fn advance(component_manager: &mut ComponentManager, bottom: f32) {
+    for (_, (transform, rigidbody)) in component_manager.query_mut::<(&mut Transform, &Rigidbody)>() {
+        transform.prev_pos = transform.pos;
+        let pos = transform.pos + rigidbody.vel * STEP;
+
+        if pos.y > bottom {
+            transform.teleport(Vector2::new(pos.x, -BLOCK));
+        }
+        else {
+            transform.set_pos(pos);
+        }
+    }
+}
+
+fn collision_step(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision_step");
+
+    for blocks in [1_000, 10_000, 50_000] {
+        let (entity_system, mut component_manager, bottom) = world(blocks);
+        let mut resources = Resources::new();
+        let mut system = CollisionSystem::default();
+
+        // the first step files every block in the broadphase, which only ever
+        // happens once and isn't what's being measured
+        system.step(&entity_system, &mut component_manager, &mut resources);
+
+        group.bench_function(BenchmarkId::from_parameter(blocks), |b| {
+            b.iter(|| {
+                advance(&mut component_manager, bottom);
+                system.step(&entity_system, &mut component_manager, &mut resources);
+                resources.get_or_insert_default::<Events>().update();
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, collision_step);
+criterion_main!(benches);
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use ggez_test::{
+    component::ComponentList,
+    transform::Transform,
+    vector2::Vector2,
+};
+
+// the list as it was before the paged sparse index, an entity -> dense index map
+struct HashMapList<T> {
+    components: Vec<T>,
+    entities: Vec<usize>,
+    entity_map: HashMap<usize, usize>,
+}
+
+impl<T> HashMapList<T> {
+    fn new() -> Self {
+        Self {
+            components: Vec::new(),
+            entities: Vec::new(),
+            entity_map: HashMap::new(),
+        }
+    }
+
+    fn add_component(&mut self, entity: usize, component: T) {
+        self.entity_map.insert(entity, self.components.len());
+        self.entities.push(entity);
+        self.components.push(component);
+    }
+
+    fn get_entity_component(&self, entity: usize) -> Option<&T> {
+        self.entity_map.get(&entity).map(|i| &self.components[*i])
+    }
+
+    fn remove_component(&mut self, entity: usize) -> Option<T> {
+        let index = self.entity_map.remove(&entity)?;
+
+        let last = self.components.len() - 1;
+        if index != last {
+            let moved_entity = self.entities[last];
+            self.entity_map.insert(moved_entity, index);
+        }
+
+        self.entities.swap_remove(index);
+        Some(self.components.swap_remove(index))
+    }
+}
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+
+fn transform(entity: usize) -> Transform {
+    Transform::new(Vector2::new(entity as f32, 0.0))
+}
+
+// what a two-component query does for every entity of the smaller list:
+// look the entity up in the other one
+fn lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+
+    for size in SIZES {
+        // every other entity, so half the lookups miss
+        let probes: Vec<usize> = (0..size).map(|i| i * 2).collect();
+
+        let mut hash_map = HashMapList::new();
+        let mut sparse = ComponentList::new();
+        for entity in 0..size {
+            hash_map.add_component(entity, transform(entity));
+            sparse.add_component(entity, transform(entity));
+        }
+
+        group.bench_with_input(BenchmarkId::new("hash_map", size), &probes, |b, probes| {
+            b.iter(|| {
+                let mut sum = 0.0;
+                for entity in probes {
+                    if let Some(t) = hash_map.get_entity_component(*entity) {
+                        sum += t.pos.x;
+                    }
+                }
+                black_box(sum)
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("sparse", size), &probes, |b, probes| {
+            b.iter(|| {
+                let mut sum = 0.0;
+                for entity in probes {
+                    if let Some(t) = sparse.get_entity_component(*entity) {
+                        sum += t.pos.x;
+                    }
+                }
+                black_box(sum)
+            });
+        });
+    }
+
+    group.finish();
+}
+
+// filling a list and then removing every other entity from it
+fn insert_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_remove");
+
+    for size in SIZES {
+        group.bench_with_input(BenchmarkId::new("hash_map", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut list = HashMapList::new();
+                for entity in 0..size {
+                    list.add_component(entity, transform(entity));
+                }
+                for entity in (0..size).step_by(2) {
+                    list.remove_component(entity);
+                }
+                black_box(list.components.len())
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("sparse", size), &size, |b, &size| {
+            b.iter(|| {
+                let mut list = ComponentList::new();
+                for entity in 0..size {
+                    list.add_component(entity, transform(entity));
+                }
+                for entity in (0..size).step_by(2) {
+                    list.remove_component(entity);
+                }
+                black_box(list.len())
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, lookup, insert_remove);
+criterion_main!(benches);
@@ -1,11 +1,22 @@
-use ggez::{Context, GameResult, graphics::{self, Color}, mint::Vector2 as Vector2f};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use ggez::{Context, GameResult, graphics::{self, Color}, mint::{Vector2 as Vector2f, Point2}};
 
 use crate::{
     system::System,
-    component::{ComponentManager, Component},
-    transform::Transform, rigidbody::Rigidbody, vector2::Vector2
+    resource::Resources,
+    events::{Event, Events},
+    component::{ComponentManager, Component, ComponentList},
+    entity::{Entity, EntitySystem},
+    collider::{self, Collider, Shape},
+    transform::Transform, rigidbody::{Rigidbody, BodyType}, vector2::Vector2,
+    broadphase::SpatialHash,
+    platformer::PlatformerController,
+    commands::Commands,
 };
 
+pub const LAYER_DEFAULT: u32 = 1;
+pub const LAYER_ALL: u32 = u32::MAX;
 
 #[derive(Copy, Clone)]
 pub struct AABB {
@@ -13,6 +24,13 @@ pub struct AABB {
   pub y: f32,
   pub w: f32,
   pub h: f32,
+  // bitfields: two boxes only collide if each one's layer is in the other's mask
+  pub layer: u32,
+  pub mask: u32,
+  // triggers report overlaps through `CollisionEvents` but never block anything
+  pub is_trigger: bool,
+  // one-way platforms only block things landing on them from above
+  pub one_way: bool,
 }
 
 #[allow(dead_code)]
@@ -24,21 +42,115 @@ impl AABB {
         self.y + self.h > b.y
     }
 
+    pub fn collides_with(&self, b: &AABB) -> bool {
+        self.layer & b.mask != 0 && b.layer & self.mask != 0
+    }
+
     pub fn translate(&self, dir: Vector2) -> AABB {
         Self {
             x: self.x + dir.x,
             y: self.y + dir.y,
-            w: self.w,
-            h: self.h,
+            ..*self
         }
     }
 
+    pub fn merge(&self, b: AABB) -> AABB {
+        let x = self.x.min(b.x);
+        let y = self.y.min(b.y);
+
+        Self {
+            x,
+            y,
+            w: (self.x + self.w).max(b.x + b.w) - x,
+            h: (self.y + self.h).max(b.y + b.h) - y,
+            ..*self
+        }
+    }
+
+    // positions the box so that it's centered on `pos`, the same way sprites are drawn
+    pub fn centered_at(&self, pos: Vector2) -> AABB {
+        Self {
+            x: pos.x - self.w * 0.5,
+            y: pos.y - self.h * 0.5,
+            ..*self
+        }
+    }
+
+    // the axis of least overlap between two boxes, as a normal pointing from
+    // `self` towards `b` and the depth along it
+    pub fn penetration(&self, b: AABB) -> Option<(Vector2, f32)> {
+        let overlap_x = (self.x + self.w).min(b.x + b.w) - self.x.max(b.x);
+        let overlap_y = (self.y + self.h).min(b.y + b.h) - self.y.max(b.y);
+
+        if overlap_x <= 0.0 || overlap_y <= 0.0 {
+            return None;
+        }
+
+        let dx = (b.x + b.w * 0.5) - (self.x + self.w * 0.5);
+        let dy = (b.y + b.h * 0.5) - (self.y + self.h * 0.5);
+
+        if overlap_x < overlap_y {
+            let nx = if dx < 0.0 { -1.0 } else { 1.0 };
+            Some((Vector2::new(nx, 0.0), overlap_x))
+        }
+        else {
+            let ny = if dy < 0.0 { -1.0 } else { 1.0 };
+            Some((Vector2::new(0.0, ny), overlap_y))
+        }
+    }
+
+    // time of impact in [0, 1] of this box moving by `dir` against a stationary `b`,
+    // along with the normal of the face that was hit. boxes already overlapping
+    // at the start of the move don't count as a hit
+    pub fn sweep(&self, dir: Vector2, b: AABB) -> Option<(f32, Vector2)> {
+        let (entry_x, exit_x) = if dir.x > 0.0 {
+            ((b.x - (self.x + self.w)) / dir.x, (b.x + b.w - self.x) / dir.x)
+        }
+        else if dir.x < 0.0 {
+            ((b.x + b.w - self.x) / dir.x, (b.x - (self.x + self.w)) / dir.x)
+        }
+        else if self.x < b.x + b.w && self.x + self.w > b.x {
+            (f32::NEG_INFINITY, f32::INFINITY)
+        }
+        else {
+            return None;
+        };
+
+        let (entry_y, exit_y) = if dir.y > 0.0 {
+            ((b.y - (self.y + self.h)) / dir.y, (b.y + b.h - self.y) / dir.y)
+        }
+        else if dir.y < 0.0 {
+            ((b.y + b.h - self.y) / dir.y, (b.y - (self.y + self.h)) / dir.y)
+        }
+        else if self.y < b.y + b.h && self.y + self.h > b.y {
+            (f32::NEG_INFINITY, f32::INFINITY)
+        }
+        else {
+            return None;
+        };
+
+        let entry = entry_x.max(entry_y);
+        let exit = exit_x.min(exit_y);
+
+        if entry > exit || !(0.0..=1.0).contains(&entry) {
+            return None;
+        }
+
+        let normal = if entry_x > entry_y {
+            Vector2::new(-dir.x.signum(), 0.0)
+        }
+        else {
+            Vector2::new(0.0, -dir.y.signum())
+        };
+
+        Some((entry, normal))
+    }
+
     pub fn at(&self, pos: Vector2) -> AABB {
         Self {
             x: pos.x,
             y: pos.y,
-            w: self.w,
-            h: self.h,
+            ..*self
         }
     }
 }
@@ -50,131 +162,961 @@ impl Default for AABB {
       y: 0.0,
       w: 32.0,
       h: 32.0,
+      layer: LAYER_DEFAULT,
+      mask: LAYER_ALL,
+      is_trigger: false,
+      one_way: false,
     }    
   }  
 }
 
 impl Component for AABB {}
 
-pub struct CollisionSystem {}
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CollisionEventKind {
+    Enter,
+    Stay,
+    Exit,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub struct CollisionEvent {
+    pub kind: CollisionEventKind,
+    pub entity: Entity,
+    pub other: Entity,
+}
 
-impl Default for CollisionSystem {
-    fn default() -> Self {
-        Self {}    
-    }  
+impl Event for CollisionEvent {}
+
+// opt-in inbox: entities with this component get the trigger events they took
+// part in during the last simulation step, with `entity` set to themselves.
+// systems that want every event read them from the `Events` resource instead
+#[derive(Clone, Default)]
+pub struct CollisionEvents {
+    pub events: Vec<CollisionEvent>,
+}
+
+impl Component for CollisionEvents {}
+
+// one resolved collision between two solid bodies during a simulation step
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+pub struct Contact {
+    pub entity: Entity,
+    pub other: Entity,
+    // points from `entity` towards `other`
+    pub normal: Vector2,
+    pub penetration: f32,
+    // velocity of `other` relative to `entity` before the hit was resolved
+    pub relative_vel: Vector2,
+}
+
+impl Event for Contact {}
+
+impl Contact {
+    // the same contact seen from the other side
+    pub fn flipped(&self) -> Contact {
+        Contact {
+            entity: self.other,
+            other: self.entity,
+            normal: -self.normal,
+            penetration: self.penetration,
+            relative_vel: -self.relative_vel,
+        }
+    }
+}
+
+// opt-in inbox: entities with this component get every contact they took part
+// in during the last simulation step, with `entity` set to themselves
+#[derive(Clone, Default)]
+pub struct Contacts {
+    pub contacts: Vec<Contact>,
+}
+
+impl Component for Contacts {}
+
+// minimum vertical component of a surface normal for it to count as floor or ceiling
+const GROUND_NORMAL_Y: f32 = 0.5;
+
+// opt-in summary of what the entity touched during the last simulation step
+#[derive(Copy, Clone, Default)]
+pub struct ContactFlags {
+    pub grounded: bool,
+    pub ceiling: bool,
+    pub wall_left: bool,
+    pub wall_right: bool,
+    // normal of the surface being stood on, pointing away from it
+    pub ground_normal: Vector2,
+}
+
+impl ContactFlags {
+    // `normal` points from the surface towards the entity
+    fn touch(&mut self, normal: Vector2) {
+        if normal.y <= -GROUND_NORMAL_Y {
+            if !self.grounded || normal.y < self.ground_normal.y {
+                self.ground_normal = normal;
+            }
+            self.grounded = true;
+        }
+        else if normal.y >= GROUND_NORMAL_Y {
+            self.ceiling = true;
+        }
+        else if normal.x > 0.0 {
+            self.wall_left = true;
+        }
+        else {
+            self.wall_right = true;
+        }
+    }
+}
+
+impl Component for ContactFlags {}
+
+#[derive(Copy, Clone)]
+struct Body {
+    entity: usize,
+    body_type: BodyType,
+    prev_pos: Vector2,
+    pos: Vector2,
+    vel: Vector2,
+    aabb: AABB,
+    inv_mass: f32,
+    restitution: f32,
+    friction: f32,
+    // set for characters: the minimum upward normal of ground they can stand on
+    min_ground_normal: Option<f32>,
+}
+
+impl Body {
+    // colliders without a rigidbody act as immovable walls
+    fn fixed(entity: usize, aabb: AABB) -> Self {
+        let material = Rigidbody::default();
+
+        let pos = Vector2::new(aabb.x + aabb.w * 0.5, aabb.y + aabb.h * 0.5);
+
+        Self {
+            entity,
+            body_type: BodyType::Static,
+            prev_pos: pos,
+            pos,
+            vel: Vector2::default(),
+            aabb,
+            inv_mass: 0.0,
+            restitution: material.restitution,
+            friction: material.friction,
+            min_ground_normal: None,
+        }
+    }
+
+    fn translate(&mut self, offset: Vector2) {
+        self.pos = self.pos + offset;
+        self.aabb = self.aabb.translate(offset);
+    }
+}
+
+// pushes the bodies apart along `normal` (pointing from a to b) in proportion to
+// their inverse masses, then applies a restitution impulse and coulomb friction
+fn resolve_contact(a: &mut Body, b: &mut Body, normal: Vector2, depth: f32) {
+    let total_inv_mass = a.inv_mass + b.inv_mass;
+    if total_inv_mass <= 0.0 {
+        return;
+    }
+
+    let correction = normal * (depth / total_inv_mass);
+    a.translate(-correction * a.inv_mass);
+    b.translate(correction * b.inv_mass);
+
+    let relative_vel = b.vel - a.vel;
+    let vel_along_normal = relative_vel.dot(normal);
+    if vel_along_normal > 0.0 {
+        return;
+    }
+
+    let restitution = a.restitution.max(b.restitution);
+    let j = -(1.0 + restitution) * vel_along_normal / total_inv_mass;
+    let impulse = normal * j;
+    a.vel = a.vel - impulse * a.inv_mass;
+    b.vel = b.vel + impulse * b.inv_mass;
+
+    let relative_vel = b.vel - a.vel;
+    let tangent = (relative_vel - normal * relative_vel.dot(normal)).normalize();
+    let friction = (a.friction * b.friction).sqrt();
+    let jt = (-relative_vel.dot(tangent) / total_inv_mass).clamp(-j * friction, j * friction);
+    let friction_impulse = tangent * jt;
+    a.vel = a.vel - friction_impulse * a.inv_mass;
+    b.vel = b.vel + friction_impulse * b.inv_mass;
+}
+
+// characters are pushed straight up out of walkable ground instead of along the
+// contact normal, otherwise gravity makes them creep down slopes while standing still.
+// `surface_normal` points from the ground towards the body
+fn resolve_character(body: &mut Body, surface_normal: Vector2, depth: f32) {
+    body.translate(Vector2::new(0.0, depth / surface_normal.y));
+
+    let vel_along_normal = body.vel.dot(surface_normal);
+    if vel_along_normal < 0.0 {
+        body.vel = body.vel - surface_normal * vel_along_normal;
+    }
+}
+
+// how far into a one-way platform something can be and still count as landing on it
+const ONE_WAY_TOLERANCE: f32 = 4.0;
+
+// `normal` points from the mover towards the platform
+fn one_way_blocks(mover: &Body, platform: &Body, normal: Vector2) -> bool {
+    let prev_bottom = mover.prev_pos.y + mover.aabb.h * 0.5;
+    normal.y >= GROUND_NORMAL_Y && prev_bottom <= platform.aabb.y + ONE_WAY_TOLERANCE
+}
+
+// buffers reused by every shape test so the narrowphase doesn't allocate per pair
+#[derive(Default)]
+struct ShapeScratch {
+    a: Shape,
+    b: Shape,
+    axes: Vec<Vector2>,
+}
+
+// resolves through the shape narrowphase when either side has a non-box `Collider`
+fn narrowphase(shapes: Option<&ComponentList<Collider>>, scratch: &mut ShapeScratch, a: &Body, b: &Body) -> Option<(Vector2, f32)> {
+    let shape_a = shapes.and_then(|s| s.get_entity_component(a.entity));
+    let shape_b = shapes.and_then(|s| s.get_entity_component(b.entity));
+
+    if is_box(shape_a) && is_box(shape_b) {
+        return a.aabb.penetration(b.aabb);
+    }
+
+    scratch.a.set(shape_a, &a.aabb, a.pos);
+    scratch.b.set(shape_b, &b.aabb, b.pos);
+    collider::collide(&scratch.a, &scratch.b, &mut scratch.axes)
+}
+
+fn is_box(shape: Option<&Collider>) -> bool {
+    matches!(shape, None | Some(Collider::Aabb))
+}
+
+const SWEEP_ITERATIONS: usize = 3;
+
+// how deep a body can still be stuck in something immovable after resolution
+// before it counts as crushed between a moving body and a wall
+const CRUSH_DEPTH: f32 = 8.0;
+
+#[derive(Default)]
+pub struct CollisionSystem {
+    contacts: Vec<Contact>,
+    // handles rather than indices: an exit can be reported after one side is gone
+    // and its index has been handed to someone else
+    overlaps: BTreeSet<(Entity, Entity)>,
+    prev_overlaps: BTreeSet<(Entity, Entity)>,
+    events: Vec<CollisionEvent>,
+    bodies: Vec<Body>,
+    body_lookup: HashMap<usize, usize>,
+    // every box by entity, as it was filed in the broadphase at the start of the step.
+    // both persist between steps so only boxes that moved have to be refiled
+    colliders: Vec<Option<AABB>>,
+    broadphase: SpatialHash,
+    candidates: Vec<usize>,
+    scratch: ShapeScratch,
+    carried: Vec<bool>,
+    carry_queue: VecDeque<(usize, Vector2)>,
+    pushed: Vec<usize>,
+    crushed: Vec<Entity>,
+}
+
+impl CollisionSystem {
+    // moves everything standing on a kinematic body along with it, and anything
+    // standing on those in turn. who stands on what comes from the contacts of the
+    // previous step, so this has to run before they're cleared
+    fn carry_riders(&mut self, entities: &EntitySystem) {
+        self.carried.clear();
+        self.carried.resize(self.bodies.len(), false);
+
+        self.carry_queue.clear();
+        for (i, body) in self.bodies.iter().enumerate() {
+            let offset = body.pos - body.prev_pos;
+            if body.body_type == BodyType::Kinematic && offset.dot(offset) > 0.0 {
+                self.carried[i] = true;
+                self.carry_queue.push_back((body.entity, offset));
+            }
+        }
+
+        while let Some((platform, offset)) = self.carry_queue.pop_front() {
+            for c in &self.contacts {
+                // either side may have been despawned since, and its index reused
+                if !entities.is_alive(c.entity) || !entities.is_alive(c.other) {
+                    continue;
+                }
+
+                // `normal` points from `entity` to `other`, so a downward normal
+                // means `entity` is standing on `other` and the other way around
+                let rider = if c.other.index == platform && c.normal.y >= GROUND_NORMAL_Y {
+                    c.entity.index
+                }
+                else if c.entity.index == platform && c.normal.y <= -GROUND_NORMAL_Y {
+                    c.other.index
+                }
+                else {
+                    continue;
+                };
+
+                let Some(&i) = self.body_lookup.get(&rider) else {
+                    continue;
+                };
+                if self.carried[i] || self.bodies[i].body_type != BodyType::Dynamic {
+                    continue;
+                }
+
+                self.carried[i] = true;
+                self.bodies[i].translate(offset);
+                self.carry_queue.push_back((rider, offset));
+            }
+        }
+    }
+
+    // whether a body pushed by a kinematic one this step is still stuck inside
+    // something it can't move, i.e. it got squeezed against a wall or the floor
+    fn is_crushed(&mut self, i: usize, shapes: Option<&ComponentList<Collider>>) -> bool {
+        let body = self.bodies[i];
+        self.broadphase.query(&body.aabb, &mut self.candidates);
+
+        self.candidates.iter().any(|c| {
+            let entity_b = *c;
+            let Some(aabb_b) = self.colliders[entity_b] else {
+                return false;
+            };
+            if entity_b == body.entity || aabb_b.is_trigger || aabb_b.one_way || !body.aabb.collides_with(&aabb_b) {
+                return false;
+            }
+
+            let b = match self.body_lookup.get(&entity_b) {
+                Some(j) if self.bodies[*j].inv_mass > 0.0 => return false,
+                Some(j) => self.bodies[*j],
+                None => Body::fixed(entity_b, aabb_b),
+            };
+
+            narrowphase(shapes, &mut self.scratch, &body, &b).is_some_and(|(_, depth)| depth > CRUSH_DEPTH)
+        })
+    }
+
+    // continuous collision against everything immovable, i.e. colliders without a
+    // rigidbody and static or kinematic bodies, the latter where they are now:
+    // sweeps the body's box from where it started the step to where physics put it,
+    // stops at the earliest hit and slides the rest of the way along the surface.
+    // this is what keeps fast bodies from tunnelling through anything thinner than
+    // one step of motion. only boxes are swept, other shapes rely on the discrete narrowphase
+    fn sweep_body(&mut self, i: usize, entities: &EntitySystem, shapes: Option<&ComponentList<Collider>>) {
+        let mut body = self.bodies[i];
+        if body.body_type != BodyType::Dynamic || !is_box(shapes.and_then(|s| s.get_entity_component(body.entity))) {
+            return;
+        }
+
+        let mut start = body.aabb.centered_at(body.prev_pos);
+        let mut remaining = body.pos - body.prev_pos;
+
+        for _ in 0..SWEEP_ITERATIONS {
+            if remaining.dot(remaining) <= f32::EPSILON {
+                break;
+            }
+
+            self.broadphase.query(&start.merge(start.translate(remaining)), &mut self.candidates);
+
+            let mut earliest: Option<(f32, Vector2, usize, Vector2)> = None;
+            for c in &self.candidates {
+                let entity_b = *c;
+                let Some(aabb_b) = self.colliders[entity_b] else {
+                    continue;
+                };
+                if entity_b == body.entity ||
+                    aabb_b.is_trigger ||
+                    body.aabb.is_trigger ||
+                    !is_box(shapes.and_then(|s| s.get_entity_component(entity_b))) ||
+                    !body.aabb.collides_with(&aabb_b) {
+                    continue;
+                }
+
+                // other movable bodies are left to the narrowphase
+                let (aabb_b, vel_b) = match self.body_lookup.get(&entity_b) {
+                    Some(j) if self.bodies[*j].inv_mass > 0.0 => continue,
+                    Some(j) => (self.bodies[*j].aabb, self.bodies[*j].vel),
+                    None => (aabb_b, Vector2::default()),
+                };
+
+                if let Some((t, normal)) = start.sweep(remaining, aabb_b) {
+                    if aabb_b.one_way && normal.y >= 0.0 {
+                        continue;
+                    }
+
+                    if earliest.is_none_or(|(e, _, _, _)| t < e) {
+                        earliest = Some((t, normal, entity_b, vel_b));
+                    }
+                }
+            }
+
+            match earliest {
+                Some((t, normal, entity_b, vel_b)) => {
+                    if let (Some(entity), Some(other)) = (entities.entity(body.entity), entities.entity(entity_b)) {
+                        self.contacts.push(Contact {
+                            entity,
+                            other,
+                            normal: -normal,
+                            penetration: 0.0,
+                            relative_vel: vel_b - body.vel,
+                        });
+                    }
+
+                    start = start.translate(remaining * t);
+                    remaining = remaining * (1.0 - t);
+                    remaining = remaining - normal * remaining.dot(normal);
+
+                    let vel_along_normal = body.vel.dot(normal);
+                    if vel_along_normal < 0.0 {
+                        body.vel = body.vel - normal * ((1.0 + body.restitution) * vel_along_normal);
+                    }
+                },
+                None => {
+                    start = start.translate(remaining);
+                    break;
+                }
+            }
+        }
+
+        body.pos = Vector2::new(start.x + start.w * 0.5, start.y + start.h * 0.5);
+        body.aabb = start;
+        self.bodies[i] = body;
+    }
+
+    // syncs every box with its transform and collects this step's bodies and
+    // colliders, moving riders along with whatever they stand on
+    fn prepare(&mut self, entities: &EntitySystem, component_manager: &mut ComponentManager) {
+        for (_, (transform, aabb, shape)) in component_manager.query_mut::<(&Transform, &mut AABB, Option<&Collider>)>() {
+            if let Some(shape) = shape {
+                let extents = shape.half_extents(aabb);
+                aabb.w = extents.x * 2.0;
+                aabb.h = extents.y * 2.0;
+            }
+            *aabb = aabb.centered_at(transform.pos);
+        }
+
+        self.bodies.clear();
+        self.body_lookup.clear();
+        for (entity, (transform, rigidbody, aabb, controller)) in component_manager.query::<(&Transform, &Rigidbody, &AABB, Option<&PlatformerController>)>() {
+            self.body_lookup.insert(entity, self.bodies.len());
+            self.bodies.push(Body {
+                entity,
+                body_type: rigidbody.body_type,
+                prev_pos: transform.prev_pos,
+                pos: transform.pos,
+                vel: rigidbody.vel,
+                aabb: *aabb,
+                inv_mass: rigidbody.effective_inv_mass(),
+                restitution: rigidbody.restitution,
+                friction: rigidbody.friction,
+                min_ground_normal: controller.map(|c| c.min_ground_normal()),
+            });
+        }
+
+        self.carry_riders(entities);
+        self.contacts.clear();
+        self.pushed.clear();
+
+        // most colliders are level geometry that never moves, so the broadphase is
+        // kept between steps and only boxes that changed cells get refiled
+        // a rigidbody without a transform isn't a body, so its box is taken as is
+        for (entity, aabb) in component_manager.query::<&AABB>() {
+            let aabb = self.body_lookup.get(&entity).map_or(*aabb, |i| self.bodies[*i].aabb);
+
+            if entity >= self.colliders.len() {
+                self.colliders.resize(entity + 1, None);
+            }
+
+            match self.colliders[entity] {
+                Some(old) => self.broadphase.update(entity, &old, &aabb),
+                None => self.broadphase.insert(entity, &aabb),
+            }
+            self.colliders[entity] = Some(aabb);
+        }
+
+        // boxes that were removed or despawned since the last step
+        let aabbs = component_manager.get_components::<AABB>();
+        for (entity, collider) in self.colliders.iter_mut().enumerate() {
+            if let Some(old) = collider {
+                if !aabbs.is_some_and(|a| a.contains(entity)) {
+                    self.broadphase.remove(entity, old);
+                    *collider = None;
+                }
+            }
+        }
+    }
+
+    // everything `update` does, which doesn't need the `Context`, so the collision
+    // step can also be driven headless, e.g. by the benchmarks
+    pub fn step(&mut self, entities: &EntitySystem, component_manager: &mut ComponentManager, resources: &mut Resources) {
+        // the grid is only lent to the resources between steps. if it went
+        // missing, everything has to be filed again
+        match resources.remove::<SpatialHash>() {
+            Some(grid) => self.broadphase = grid,
+            None => self.colliders.clear(),
+        }
+
+        self.prepare(entities, component_manager);
+
+        let shapes = component_manager.get_components::<Collider>();
+
+        for i in 0..self.bodies.len() {
+            self.sweep_body(i, entities, shapes);
+        }
+
+        std::mem::swap(&mut self.overlaps, &mut self.prev_overlaps);
+        self.overlaps.clear();
+
+        for i in 0..self.bodies.len() {
+            // static bodies only ever take part as the other side of a pair
+            if self.bodies[i].body_type == BodyType::Static {
+                continue;
+            }
+
+            self.broadphase.query(&self.bodies[i].aabb, &mut self.candidates);
+
+            for c in &self.candidates {
+                let entity_b = *c;
+                let Some(aabb_b) = self.colliders[entity_b] else {
+                    continue;
+                };
+                if entity_b == self.bodies[i].entity || !self.bodies[i].aabb.collides_with(&aabb_b) {
+                    continue;
+                }
+
+                let other = self.body_lookup.get(&entity_b).copied();
+
+                // pairs of moving bodies are resolved once, from the lower index
+                if other.is_some_and(|j| j < i && self.bodies[j].body_type != BodyType::Static) {
+                    continue;
+                }
+
+                let mut a = self.bodies[i];
+                let mut b = match other {
+                    Some(j) => self.bodies[j],
+                    None => Body::fixed(entity_b, aabb_b),
+                };
+
+                let contact = narrowphase(shapes, &mut self.scratch, &a, &b);
+
+                if a.aabb.is_trigger || b.aabb.is_trigger {
+                    if let (Some(_), Some(a), Some(b)) = (contact, entities.entity(a.entity), entities.entity(b.entity)) {
+                        self.overlaps.insert((a.min(b), a.max(b)));
+                    }
+                    continue;
+                }
+
+                if let Some((normal, depth)) = contact {
+                    let relative_vel = b.vel - a.vel;
+                    if b.aabb.one_way && !one_way_blocks(&a, &b, normal) {
+                        continue;
+                    }
+                    if a.aabb.one_way && !one_way_blocks(&b, &a, -normal) {
+                        continue;
+                    }
+
+                    if a.inv_mass > 0.0 && b.inv_mass == 0.0 && a.min_ground_normal.is_some_and(|min| normal.y >= min) {
+                        resolve_character(&mut a, -normal, depth);
+                    }
+                    else if b.inv_mass > 0.0 && a.inv_mass == 0.0 && b.min_ground_normal.is_some_and(|min| -normal.y >= min) {
+                        resolve_character(&mut b, normal, depth);
+                    }
+                    else {
+                        resolve_contact(&mut a, &mut b, normal, depth);
+                    }
+
+                    if let (Some(entity), Some(other)) = (entities.entity(a.entity), entities.entity(b.entity)) {
+                        self.contacts.push(Contact {
+                            entity,
+                            other,
+                            normal,
+                            penetration: depth,
+                            relative_vel,
+                        });
+                    }
+
+                    if a.body_type == BodyType::Kinematic && b.inv_mass > 0.0 {
+                        self.pushed.extend(other);
+                    }
+                    if b.body_type == BodyType::Kinematic && a.inv_mass > 0.0 {
+                        self.pushed.push(i);
+                    }
+
+                    self.bodies[i] = a;
+                    if let Some(j) = other {
+                        self.bodies[j] = b;
+                    }
+                }
+            }
+        }
+
+        self.crushed.clear();
+        self.pushed.sort_unstable();
+        self.pushed.dedup();
+        for k in 0..self.pushed.len() {
+            let i = self.pushed[k];
+            if self.is_crushed(i, shapes) {
+                self.crushed.extend(entities.entity(self.bodies[i].entity));
+            }
+        }
+
+        for (entity, (transform, rigidbody, aabb)) in component_manager.query_mut::<(&mut Transform, &mut Rigidbody, &mut AABB)>() {
+            if let Some(i) = self.body_lookup.get(&entity) {
+                let body = &self.bodies[*i];
+                transform.pos = body.pos;
+                rigidbody.vel = body.vel;
+                *aabb = body.aabb;
+            }
+        }
+
+        // bodies are refiled where they ended up, so queries between steps see
+        // the grid agree with the boxes
+        for body in &self.bodies {
+            if let Some(old) = self.colliders[body.entity] {
+                self.broadphase.update(body.entity, &old, &body.aabb);
+            }
+            self.colliders[body.entity] = Some(body.aabb);
+        }
+        resources.insert(std::mem::take(&mut self.broadphase));
+
+        let commands = resources.get_or_insert_default::<Commands>();
+        for entity in &self.crushed {
+            commands.despawn(*entity);
+        }
+
+        if let Some(flags) = component_manager.get_components_mut::<ContactFlags>() {
+            for f in flags.iter_mut() {
+                *f = ContactFlags::default();
+            }
+
+            for c in &self.contacts {
+                if let Some(f) = flags.get_entity_component_mut(c.entity.index) {
+                    f.touch(-c.normal);
+                }
+                if let Some(f) = flags.get_entity_component_mut(c.other.index) {
+                    f.touch(c.normal);
+                }
+            }
+        }
+
+        if let Some(inboxes) = component_manager.get_components_mut::<Contacts>() {
+            for inbox in inboxes.iter_mut() {
+                inbox.contacts.clear();
+            }
+
+            for c in &self.contacts {
+                if let Some(inbox) = inboxes.get_entity_component_mut(c.entity.index) {
+                    inbox.contacts.push(*c);
+                }
+                if let Some(inbox) = inboxes.get_entity_component_mut(c.other.index) {
+                    inbox.contacts.push(c.flipped());
+                }
+            }
+        }
+
+        self.events.clear();
+        for (a, b) in &self.overlaps {
+            let kind = if self.prev_overlaps.contains(&(*a, *b)) {
+                CollisionEventKind::Stay
+            }
+            else {
+                CollisionEventKind::Enter
+            };
+            self.events.push(CollisionEvent { kind, entity: *a, other: *b });
+        }
+        for (a, b) in self.prev_overlaps.difference(&self.overlaps) {
+            self.events.push(CollisionEvent { kind: CollisionEventKind::Exit, entity: *a, other: *b });
+        }
+
+        if let Some(inboxes) = component_manager.get_components_mut::<CollisionEvents>() {
+            for inbox in inboxes.iter_mut() {
+                inbox.events.clear();
+            }
+
+            for event in &self.events {
+                if entities.is_alive(event.entity) {
+                    if let Some(inbox) = inboxes.get_entity_component_mut(event.entity.index) {
+                        inbox.events.push(*event);
+                    }
+                }
+                if entities.is_alive(event.other) {
+                    if let Some(inbox) = inboxes.get_entity_component_mut(event.other.index) {
+                        inbox.events.push(CollisionEvent { entity: event.other, other: event.entity, ..*event });
+                    }
+                }
+            }
+        }
+
+        let bus = resources.get_or_insert_default::<Events>();
+        for event in &self.events {
+            bus.send(*event);
+        }
+        for contact in &self.contacts {
+            bus.send(*contact);
+        }
+    }
 }
 
 impl System for CollisionSystem {
-    fn update(&mut self, _: &mut Context, entities: usize, component_manager: &mut ComponentManager) {
-        for entity in 0..entities {
-            let has_transform_component = component_manager.entity_has_component::<Transform>(entity);
-            let has_rigidbody_component = component_manager.entity_has_component::<Rigidbody>(entity);
-            let has_aabb_component = component_manager.entity_has_component::<AABB>(entity);
-
-            if has_transform_component && has_aabb_component {
-                let transform_list = component_manager.get_components::<Transform>().unwrap();
-                let transform = *transform_list.get_entity_component(entity).unwrap();
-
-                let aabb_list_mut = component_manager.get_components_mut::<AABB>().unwrap();
-                let aabb_ref = aabb_list_mut.get_entity_component_mut(entity).unwrap();
-
-                aabb_ref.x = transform.pos.x;
-                aabb_ref.y = transform.pos.y;
-                let aabb = *aabb_ref;
-
-                if has_rigidbody_component {
-                    let rigidbody_list = component_manager.get_components::<Rigidbody>().unwrap();
-                    let rigidbody = *rigidbody_list.get_entity_component(entity).unwrap();
-                    let vel = rigidbody.vel;
-
-                    let new_position_x = Vector2::new(
-                        transform.pos.x + vel.x,
-                        transform.pos.y
-                    );
-                    let new_position_y = Vector2::new(
-                        transform.pos.x,
-                        transform.pos.y + vel.y
-                    );
-
-                    let aabb_x = aabb.at(new_position_x);
-                    let aabb_y = aabb.at(new_position_y);
-
-                    let aabb_list = component_manager.get_components::<AABB>().unwrap().clone();
-
-                    for e in 0..entities {
-                        if e == entity {
-                            continue;
-                        }
-
-                        let aabb_b = *aabb_list.get_entity_component(e).unwrap();
-
-                        if aabb_x.check(aabb_b) {
-                            let rigidbody_list_mut = component_manager.get_components_mut::<Rigidbody>().unwrap();
-                            let rigidbody_ref = rigidbody_list_mut.get_entity_component_mut(entity).unwrap();
-                            rigidbody_ref.vel.x = 0.0;
-    
-                            let transform_list_mut = component_manager.get_components_mut::<Transform>().unwrap();
-                            let transform_ref = transform_list_mut.get_entity_component_mut(entity).unwrap();
-
-                            if vel.x > 0.1 {
-                                transform_ref.pos.x = aabb_b.x - aabb.w;
-                            }
-                            else if vel.x < 0.1 {
-                                transform_ref.pos.x = aabb_b.x + aabb_b.w;
-                            }
-                        }
-
-                        if aabb_y.check(aabb_b) {
-                            let rigidbody_list_mut = component_manager.get_components_mut::<Rigidbody>().unwrap();
-                            let rigidbody_ref = rigidbody_list_mut.get_entity_component_mut(entity).unwrap();
-                            rigidbody_ref.vel.y = 0.0;
-    
-                            let transform_list_mut = component_manager.get_components_mut::<Transform>().unwrap();
-                            let transform_ref = transform_list_mut.get_entity_component_mut(entity).unwrap();
-
-                            if vel.y > 0.1 {
-                                transform_ref.pos.y = aabb_b.y - aabb.h;
-                            }
-                            else if vel.y < 0.1 {
-                                transform_ref.pos.y = aabb_b.y + aabb_b.h;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    fn draw(&self, ctx: &mut Context, entities: usize, component_manager: &ComponentManager) -> GameResult {
-        for entity in 0..entities {
-            let has_aabb_component = component_manager.entity_has_component::<AABB>(entity);
-            if has_aabb_component {
-                let aabb = component_manager.get_components::<AABB>().unwrap().get_entity_component(entity).unwrap();
-
-                let rect = graphics::Rect::new(
-                    0.0,
-                    0.0,
-                    aabb.w,
-                    aabb.h
-                );
-                let rect_mesh = graphics::Mesh::new_rectangle(
+    fn update(&mut self, _: &mut Context, entities: &EntitySystem, component_manager: &mut ComponentManager, resources: &mut Resources) {
+        self.step(entities, component_manager, resources);
+    }
+
+    fn draw(&self, ctx: &mut Context, _: &EntitySystem, component_manager: &ComponentManager, _: &Resources) -> GameResult {
+        for (_, (aabb, shape)) in component_manager.query::<(&AABB, Option<&Collider>)>() {
+            let color = if aabb.is_trigger { Color::GREEN } else { Color::RED };
+            let center = Vector2::new(aabb.x + aabb.w * 0.5, aabb.y + aabb.h * 0.5);
+
+            let mesh = match shape {
+                Some(Collider::Circle { radius }) => graphics::Mesh::new_circle(
                     ctx,
                     graphics::DrawMode::stroke(3.2),
-                    rect,
-                    Color::RED
-                )?;
+                    Point2 { x: center.x, y: center.y },
+                    *radius,
+                    0.5,
+                    color
+                )?,
+                Some(shape @ Collider::Capsule { .. }) | Some(shape @ Collider::Polygon { .. }) => {
+                    let points: Vec<Point2<f32>> = collider::outline(shape, center)
+                        .iter()
+                        .map(|p| Point2 { x: p.x, y: p.y })
+                        .collect();
 
-                let pos = Vector2f {
-                    x: aabb.x - aabb.w * 0.5,
-                    y: aabb.y - aabb.h * 0.5
-                };
-                let draw_params = graphics::DrawParam::new()
-                    .dest(pos);
+                    graphics::Mesh::new_polygon(
+                        ctx,
+                        graphics::DrawMode::stroke(3.2),
+                        &points,
+                        color
+                    )?
+                },
+                Some(Collider::Aabb) | None => graphics::Mesh::new_rectangle(
+                    ctx,
+                    graphics::DrawMode::stroke(3.2),
+                    graphics::Rect::new(aabb.x, aabb.y, aabb.w, aabb.h),
+                    color
+                )?,
+            };
 
-                graphics::draw(ctx, &rect_mesh, draw_params)?;
-            }
+            graphics::draw(ctx, &mesh, graphics::DrawParam::new().dest(Vector2f { x: 0.0, y: 0.0 }))?;
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall(x: f32, y: f32, w: f32, h: f32) -> AABB {
+        AABB { x, y, w, h, ..AABB::default() }
+    }
+
+    // a dynamic 10x10 box that starts the step centered on `from` and ends it at `to`
+    fn mover(component_manager: &mut ComponentManager, entity: usize, from: Vector2, to: Vector2) {
+        let mut transform = Transform::new(from);
+        transform.set_pos(to);
+        component_manager.insert_bundle(entity, (
+            transform,
+            Rigidbody::default(),
+            AABB { w: 10.0, h: 10.0, ..AABB::default() },
+        ));
+    }
+
+    // a collider centered on `pos`, without a rigidbody
+    fn fixed(component_manager: &mut ComponentManager, entity: usize, pos: Vector2, aabb: AABB) {
+        component_manager.insert_bundle(entity, (Transform::new(pos), aabb));
+    }
+
+    // where the swept body with the given entity ends up
+    fn swept(component_manager: &mut ComponentManager, entity: usize) -> Vector2 {
+        let entities = EntitySystem::new();
+        let mut system = CollisionSystem::default();
+        system.prepare(&entities, component_manager);
+
+        let i = system.body_lookup[&entity];
+        system.sweep_body(i, &entities, None);
+        system.bodies[i].pos
+    }
+
+    #[test]
+    fn sweep_hits_thin_wall_along_x() {
+        let moving = wall(0.0, 0.0, 10.0, 10.0);
+        let hit = moving.sweep(Vector2::new(100.0, 0.0), wall(50.0, -20.0, 1.0, 50.0));
+
+        let (t, normal) = hit.expect("the wall is in the way");
+        assert!((t - 0.4).abs() < 1e-5);
+        assert_eq!((normal.x, normal.y), (-1.0, 0.0));
+    }
+
+    #[test]
+    fn sweep_hits_thin_wall_along_y() {
+        let moving = wall(0.0, 0.0, 10.0, 10.0);
+        let hit = moving.sweep(Vector2::new(0.0, -100.0), wall(-20.0, -60.0, 50.0, 1.0));
+
+        let (t, normal) = hit.expect("the wall is in the way");
+        assert!((t - 0.59).abs() < 1e-5);
+        assert_eq!((normal.x, normal.y), (0.0, 1.0));
+    }
+
+    #[test]
+    fn sweep_misses_walls_out_of_reach() {
+        let moving = wall(0.0, 0.0, 10.0, 10.0);
+
+        assert!(moving.sweep(Vector2::new(30.0, 0.0), wall(50.0, -20.0, 1.0, 50.0)).is_none());
+        assert!(moving.sweep(Vector2::new(100.0, 0.0), wall(50.0, 20.0, 1.0, 50.0)).is_none());
+    }
+
+    #[test]
+    fn sweep_ignores_boxes_overlapping_at_start() {
+        let moving = wall(0.0, 0.0, 10.0, 10.0);
+
+        assert!(moving.sweep(Vector2::new(100.0, 0.0), wall(5.0, 0.0, 10.0, 10.0)).is_none());
+    }
+
+    #[test]
+    fn sweep_body_stops_at_thin_wall_along_x() {
+        let mut component_manager = ComponentManager::new();
+        mover(&mut component_manager, 0, Vector2::new(0.0, 0.0), Vector2::new(200.0, 0.0));
+        fixed(&mut component_manager, 1, Vector2::new(100.0, 0.0), AABB { w: 1.0, h: 100.0, ..AABB::default() });
+
+        let pos = swept(&mut component_manager, 0);
+        assert!((pos.x - 94.5).abs() < 1e-3, "ended up at {}", pos.x);
+    }
+
+    #[test]
+    fn sweep_body_stops_at_thin_floor_along_y() {
+        let mut component_manager = ComponentManager::new();
+        mover(&mut component_manager, 0, Vector2::new(0.0, 0.0), Vector2::new(0.0, 200.0));
+        fixed(&mut component_manager, 1, Vector2::new(0.0, 100.0), AABB { w: 100.0, h: 1.0, ..AABB::default() });
+
+        let pos = swept(&mut component_manager, 0);
+        assert!((pos.y - 94.5).abs() < 1e-3, "ended up at {}", pos.y);
+    }
+
+    #[test]
+    fn sweep_body_stops_at_kinematic_body() {
+        let mut component_manager = ComponentManager::new();
+        mover(&mut component_manager, 0, Vector2::new(0.0, 0.0), Vector2::new(200.0, 0.0));
+        fixed(&mut component_manager, 1, Vector2::new(100.0, 0.0), AABB { w: 1.0, h: 100.0, ..AABB::default() });
+        component_manager.insert_component(1, Rigidbody { body_type: BodyType::Kinematic, ..Rigidbody::default() });
+
+        let pos = swept(&mut component_manager, 0);
+        assert!((pos.x - 94.5).abs() < 1e-3, "ended up at {}", pos.x);
+    }
+
+    #[test]
+    fn sweep_body_passes_through_dynamic_body() {
+        let mut component_manager = ComponentManager::new();
+        mover(&mut component_manager, 0, Vector2::new(0.0, 0.0), Vector2::new(200.0, 0.0));
+        mover(&mut component_manager, 1, Vector2::new(100.0, 0.0), Vector2::new(100.0, 0.0));
+
+        let pos = swept(&mut component_manager, 0);
+        assert_eq!(pos.x, 200.0);
+    }
+
+    #[test]
+    fn sweep_body_lands_on_one_way_platform_from_above() {
+        let mut component_manager = ComponentManager::new();
+        mover(&mut component_manager, 0, Vector2::new(0.0, 0.0), Vector2::new(0.0, 200.0));
+        fixed(&mut component_manager, 1, Vector2::new(0.0, 100.0), AABB { w: 100.0, h: 1.0, one_way: true, ..AABB::default() });
+
+        let pos = swept(&mut component_manager, 0);
+        assert!((pos.y - 94.5).abs() < 1e-3, "ended up at {}", pos.y);
+    }
+
+    #[test]
+    fn sweep_body_jumps_through_one_way_platform_from_below() {
+        let mut component_manager = ComponentManager::new();
+        mover(&mut component_manager, 0, Vector2::new(0.0, 200.0), Vector2::new(0.0, 0.0));
+        fixed(&mut component_manager, 1, Vector2::new(0.0, 100.0), AABB { w: 100.0, h: 1.0, one_way: true, ..AABB::default() });
+
+        let pos = swept(&mut component_manager, 0);
+        assert_eq!(pos.y, 0.0);
+    }
+
+    #[test]
+    fn sweep_body_leaves_starting_overlap_to_the_narrowphase() {
+        let mut component_manager = ComponentManager::new();
+        mover(&mut component_manager, 0, Vector2::new(0.0, 0.0), Vector2::new(200.0, 0.0));
+        fixed(&mut component_manager, 1, Vector2::new(0.0, 0.0), AABB { w: 20.0, h: 20.0, ..AABB::default() });
+
+        let pos = swept(&mut component_manager, 0);
+        assert_eq!(pos.x, 200.0);
+    }
+
+    #[test]
+    fn prepare_files_rigidbody_without_transform_as_is() {
+        let mut component_manager = ComponentManager::new();
+        component_manager.insert_bundle(0, (Rigidbody::default(), wall(0.0, 0.0, 10.0, 10.0)));
+
+        let mut system = CollisionSystem::default();
+        system.prepare(&EntitySystem::new(), &mut component_manager);
+
+        assert!(system.body_lookup.is_empty());
+        assert_eq!(system.colliders[0].map(|a| a.x), Some(0.0));
+    }
+
+    #[test]
+    fn exit_event_keeps_the_handle_of_a_despawned_entity() {
+        let mut entities = EntitySystem::new();
+        let mut component_manager = ComponentManager::new();
+        let mut resources = Resources::new();
+        let mut system = CollisionSystem::default();
+
+        let trigger = entities.spawn((
+            Transform::new(Vector2::default()),
+            AABB { w: 50.0, h: 50.0, is_trigger: true, ..AABB::default() },
+        ), &mut component_manager);
+        let body = entities.spawn((
+            Transform::new(Vector2::default()),
+            Rigidbody::default(),
+            AABB { w: 10.0, h: 10.0, ..AABB::default() },
+        ), &mut component_manager);
+
+        system.step(&entities, &mut component_manager, &mut resources);
+        assert_eq!(system.events.len(), 1);
+        assert_eq!((system.events[0].kind, system.events[0].entity, system.events[0].other), (CollisionEventKind::Enter, trigger, body));
+
+        // the body's index goes to a new entity far away before the exit is reported
+        entities.despawn_entity(body, &mut component_manager);
+        let reused = entities.spawn((
+            Transform::new(Vector2::new(1000.0, 0.0)),
+            CollisionEvents::default(),
+        ), &mut component_manager);
+        assert_eq!(reused.index, body.index);
+
+        system.step(&entities, &mut component_manager, &mut resources);
+        assert_eq!(system.events.len(), 1);
+        assert_eq!((system.events[0].kind, system.events[0].entity, system.events[0].other), (CollisionEventKind::Exit, trigger, body));
+
+        let inbox = entities.get_component::<CollisionEvents>(reused, &component_manager).unwrap();
+        assert!(inbox.events.is_empty());
+    }
+
+    #[test]
+    fn contacts_name_both_sides_by_handle() {
+        let mut entities = EntitySystem::new();
+        let mut component_manager = ComponentManager::new();
+        let mut resources = Resources::new();
+        let mut system = CollisionSystem::default();
+
+        let floor = entities.spawn((
+            Transform::new(Vector2::new(0.0, 10.0)),
+            AABB { w: 100.0, h: 10.0, ..AABB::default() },
+        ), &mut component_manager);
+        let body = entities.spawn((
+            Transform::new(Vector2::new(0.0, 2.0)),
+            Rigidbody::default(),
+            AABB { w: 10.0, h: 10.0, ..AABB::default() },
+            Contacts::default(),
+        ), &mut component_manager);
+
+        system.step(&entities, &mut component_manager, &mut resources);
+
+        let inbox = entities.get_component::<Contacts>(body, &component_manager).unwrap();
+        assert_eq!(inbox.contacts.len(), 1);
+        assert_eq!((inbox.contacts[0].entity, inbox.contacts[0].other), (body, floor));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    vector2::Vector2,
+    resource::Resource,
+};
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BoundsBehavior {
+    // stop at the edge and lose any velocity pointing out of the world
+    Clamp,
+    // come back in from the opposite edge
+    Wrap,
+    // despawn the entity
+    Kill,
+    Unbounded,
+}
+
+// the playable area, in world units. independent of the window size
+#[derive(Copy, Clone)]
+pub struct WorldBounds {
+    pub min: Vector2,
+    pub max: Vector2,
+    pub left: BoundsBehavior,
+    pub right: BoundsBehavior,
+    pub top: BoundsBehavior,
+    pub bottom: BoundsBehavior,
+}
+
+#[allow(dead_code)]
+impl WorldBounds {
+    pub fn new(min: Vector2, max: Vector2, behavior: BoundsBehavior) -> Self {
+        Self {
+            min,
+            max,
+            left: behavior,
+            right: behavior,
+            top: behavior,
+            bottom: behavior,
+        }
+    }
+
+    pub fn unbounded() -> Self {
+        Self::new(Vector2::default(), Vector2::default(), BoundsBehavior::Unbounded)
+    }
+
+    // returns the offset a wrap applied (so interpolation can be shifted with it)
+    // and whether the position crossed a kill edge
+    pub fn apply(&self, pos: &mut Vector2, vel: &mut Vector2) -> (Vector2, bool) {
+        let size = self.max - self.min;
+        let mut offset = Vector2::default();
+        let mut killed = false;
+
+        let mut edge = |behavior: BoundsBehavior, p: &mut f32, v: &mut f32, limit: f32, wrap: f32, outward: f32| {
+            match behavior {
+                BoundsBehavior::Clamp => {
+                    *p = limit;
+                    if *v * outward > 0.0 {
+                        *v = 0.0;
+                    }
+                    0.0
+                },
+                BoundsBehavior::Wrap => {
+                    *p += wrap;
+                    wrap
+                },
+                BoundsBehavior::Kill => {
+                    killed = true;
+                    0.0
+                },
+                BoundsBehavior::Unbounded => 0.0,
+            }
+        };
+
+        if pos.x < self.min.x {
+            offset.x = edge(self.left, &mut pos.x, &mut vel.x, self.min.x, size.x, -1.0);
+        }
+        else if pos.x > self.max.x {
+            offset.x = edge(self.right, &mut pos.x, &mut vel.x, self.max.x, -size.x, 1.0);
+        }
+
+        if pos.y < self.min.y {
+            offset.y = edge(self.top, &mut pos.y, &mut vel.y, self.min.y, size.y, -1.0);
+        }
+        else if pos.y > self.max.y {
+            offset.y = edge(self.bottom, &mut pos.y, &mut vel.y, self.max.y, -size.y, 1.0);
+        }
+
+        (offset, killed)
+    }
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        Self::unbounded()
+    }
+}
+
+impl Resource for WorldBounds {}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use crate::{
+    aabb::AABB,
+    resource::Resource,
+};
+
+// uniform grid broadphase. boxes are bucketed into every cell they touch, so a
+// query only has to look at the handful of cells around the box being tested.
+// the `CollisionSystem` leaves its grid in the resources between steps, keyed
+// by entity index, for other spatial queries such as raycasts
+pub struct SpatialHash {
+    pub cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+#[allow(dead_code)]
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    // empties the buckets but keeps their allocations around for the next frame
+    pub fn clear(&mut self) {
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+    }
+
+    fn cell_range(&self, aabb: &AABB) -> (i32, i32, i32, i32) {
+        (
+            (aabb.x / self.cell_size).floor() as i32,
+            (aabb.y / self.cell_size).floor() as i32,
+            ((aabb.x + aabb.w) / self.cell_size).floor() as i32,
+            ((aabb.y + aabb.h) / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn insert(&mut self, id: usize, aabb: &AABB) {
+        let (min_x, min_y, max_x, max_y) = self.cell_range(aabb);
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: usize, aabb: &AABB) {
+        let (min_x, min_y, max_x, max_y) = self.cell_range(aabb);
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                if let Some(cell) = self.cells.get_mut(&(x, y)) {
+                    if let Some(i) = cell.iter().position(|other| *other == id) {
+                        cell.swap_remove(i);
+                    }
+                }
+            }
+        }
+    }
+
+    // refiles `id` from the cells `old` touches under the ones `new` does. boxes
+    // that moved without leaving their cells don't touch the buckets at all, and
+    // the ones that didn't move, i.e. most of them, don't even get their cells worked out
+    pub fn update(&mut self, id: usize, old: &AABB, new: &AABB) {
+        if (old.x, old.y, old.w, old.h) == (new.x, new.y, new.w, new.h) || self.cell_range(old) == self.cell_range(new) {
+            return;
+        }
+
+        self.remove(id, old);
+        self.insert(id, new);
+    }
+
+    // pushes the id of everything sharing a cell with `aabb` into `out`, once each
+    pub fn query(&self, aabb: &AABB, out: &mut Vec<usize>) {
+        out.clear();
+
+        let (min_x, min_y, max_x, max_y) = self.cell_range(aabb);
+
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    out.extend_from_slice(cell);
+                }
+            }
+        }
+
+        out.sort_unstable();
+        out.dedup();
+    }
+}
+
+impl Resource for SpatialHash {}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(64.0)
+    }
+}
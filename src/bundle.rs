@@ -0,0 +1,28 @@
+use crate::component::{Component, ComponentManager};
+
+// a group of components inserted onto an entity together, e.g. everything
+// a player needs in one `EntitySystem::spawn` call
+pub trait Bundle {
+    fn insert(self, entity: usize, component_manager: &mut ComponentManager);
+}
+
+macro_rules! impl_bundle_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($name: Component + 'static),+> Bundle for ($($name,)+) {
+            fn insert(self, entity: usize, component_manager: &mut ComponentManager) {
+                let ($($name,)+) = self;
+                $(component_manager.insert_component(entity, $name);)+
+            }
+        }
+    };
+}
+
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
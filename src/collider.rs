@@ -0,0 +1,304 @@
+use crate::{
+    aabb::AABB,
+    component::Component,
+    vector2::Vector2,
+};
+
+// narrowphase shape of an entity, centered on its transform. entities without a
+// `Collider` (or with `Collider::Aabb`) collide as the box described by their `AABB`,
+// which for every other shape is resized to the shape's bounds and used by the broadphase
+#[allow(dead_code)]
+#[derive(Clone, Default)]
+pub enum Collider {
+    #[default]
+    Aabb,
+    Circle { radius: f32 },
+    // vertical capsule: a segment from -half_height to +half_height swept by radius
+    Capsule { half_height: f32, radius: f32 },
+    // convex, in local space around the transform, any winding
+    Polygon { points: Vec<Vector2> },
+}
+
+impl Collider {
+    // half extents of the shape's bounding box
+    pub fn half_extents(&self, aabb: &AABB) -> Vector2 {
+        match self {
+            Collider::Aabb => Vector2::new(aabb.w * 0.5, aabb.h * 0.5),
+            Collider::Circle { radius } => Vector2::new(*radius, *radius),
+            Collider::Capsule { half_height, radius } => Vector2::new(*radius, half_height + radius),
+            Collider::Polygon { points } => {
+                let mut extents = Vector2::default();
+                for p in points {
+                    extents.x = extents.x.max(p.x.abs());
+                    extents.y = extents.y.max(p.y.abs());
+                }
+                extents
+            }
+        }
+    }
+}
+
+impl Component for Collider {}
+
+const CAPSULE_SEGMENTS: usize = 8;
+
+// world space outline of capsules and polygons, for debug drawing
+pub fn outline(collider: &Collider, pos: Vector2) -> Vec<Vector2> {
+    match collider {
+        Collider::Capsule { half_height, radius } => {
+            let mut points = Vec::with_capacity((CAPSULE_SEGMENTS + 1) * 2);
+            for (cap_y, start_angle) in [(-half_height, std::f32::consts::PI), (*half_height, 0.0)] {
+                for i in 0..=CAPSULE_SEGMENTS {
+                    let angle = start_angle + std::f32::consts::PI * i as f32 / CAPSULE_SEGMENTS as f32;
+                    points.push(Vector2::new(
+                        pos.x + angle.cos() * radius,
+                        pos.y + cap_y + angle.sin() * radius,
+                    ));
+                }
+            }
+            points
+        },
+        Collider::Polygon { points } => points.iter().map(|p| *p + pos).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// every supported shape is a convex core (point, segment or polygon) inflated by a
+// radius, which lets one separating axis test handle all the combinations
+#[derive(Default)]
+pub struct Shape {
+    pub points: Vec<Vector2>,
+    pub radius: f32,
+}
+
+impl Shape {
+    // rebuilds the shape in place, reusing the point buffer so testing a pair
+    // of colliders every step doesn't allocate
+    pub fn set(&mut self, collider: Option<&Collider>, aabb: &AABB, pos: Vector2) {
+        self.points.clear();
+        self.radius = 0.0;
+
+        match collider {
+            Some(Collider::Circle { radius }) => {
+                self.points.push(pos);
+                self.radius = *radius;
+            },
+            Some(Collider::Capsule { half_height, radius }) => {
+                self.points.push(Vector2::new(pos.x, pos.y - half_height));
+                self.points.push(Vector2::new(pos.x, pos.y + half_height));
+                self.radius = *radius;
+            },
+            Some(Collider::Polygon { points }) => {
+                self.points.extend(points.iter().map(|p| *p + pos));
+            },
+            Some(Collider::Aabb) | None => {
+                self.points.extend([
+                    Vector2::new(aabb.x, aabb.y),
+                    Vector2::new(aabb.x + aabb.w, aabb.y),
+                    Vector2::new(aabb.x + aabb.w, aabb.y + aabb.h),
+                    Vector2::new(aabb.x, aabb.y + aabb.h),
+                ]);
+            },
+        }
+    }
+
+    pub fn center(&self) -> Vector2 {
+        let mut sum = Vector2::default();
+        for p in &self.points {
+            sum = sum + *p;
+        }
+        sum * (1.0 / self.points.len() as f32)
+    }
+
+    fn project(&self, axis: Vector2) -> (f32, f32) {
+        let mut min = f32::INFINITY;
+        let mut max = f32::NEG_INFINITY;
+        for p in &self.points {
+            let d = p.dot(axis);
+            min = min.min(d);
+            max = max.max(d);
+        }
+        (min - self.radius, max + self.radius)
+    }
+
+    // distance along the (normalized) ray to the first hit and the surface normal
+    // there. rays starting inside the shape don't hit it
+    pub fn raycast(&self, origin: Vector2, dir: Vector2, max_dist: f32) -> Option<(f32, Vector2)> {
+        if self.radius <= 0.0 {
+            return raycast_polygon(&self.points, origin, dir, max_dist);
+        }
+
+        let mut best: Option<(f32, Vector2)> = None;
+        let mut consider = |hit: Option<(f32, Vector2)>| {
+            if let Some((t, normal)) = hit {
+                if best.is_none_or(|(b, _)| t < b) {
+                    best = Some((t, normal));
+                }
+            }
+        };
+
+        for p in &self.points {
+            consider(raycast_circle(*p, self.radius, origin, dir, max_dist));
+        }
+
+        // the straight sides of a capsule
+        if self.points.len() == 2 {
+            let axis = (self.points[1] - self.points[0]).normalize();
+            let side = Vector2::new(-axis.y, axis.x) * self.radius;
+            let rect = [
+                self.points[0] + side,
+                self.points[1] + side,
+                self.points[1] - side,
+                self.points[0] - side,
+            ];
+            consider(raycast_polygon(&rect, origin, dir, max_dist));
+        }
+
+        best
+    }
+
+    fn push_edge_normals(&self, axes: &mut Vec<Vector2>) {
+        let n = self.points.len();
+        if n < 2 {
+            return;
+        }
+
+        // a segment only has the one edge
+        let edges = if n == 2 { 1 } else { n };
+        for i in 0..edges {
+            let edge = self.points[(i + 1) % n] - self.points[i];
+            axes.push(Vector2::new(edge.y, -edge.x).normalize());
+        }
+    }
+}
+
+fn raycast_circle(center: Vector2, radius: f32, origin: Vector2, dir: Vector2, max_dist: f32) -> Option<(f32, Vector2)> {
+    let to_origin = origin - center;
+    let b = to_origin.dot(dir);
+    let c = to_origin.dot(to_origin) - radius * radius;
+
+    if c <= 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = -b - discriminant.sqrt();
+    if t < 0.0 || t > max_dist {
+        return None;
+    }
+
+    let point = origin + dir * t;
+    Some((t, (point - center).normalize()))
+}
+
+// cyrus-beck clipping of the ray against each edge of a convex polygon
+fn raycast_polygon(points: &[Vector2], origin: Vector2, dir: Vector2, max_dist: f32) -> Option<(f32, Vector2)> {
+    let n = points.len();
+    if n < 3 {
+        return None;
+    }
+
+    let mut center = Vector2::default();
+    for p in points {
+        center = center + *p;
+    }
+    center = center * (1.0 / n as f32);
+
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = max_dist;
+    let mut enter_normal = Vector2::default();
+
+    for i in 0..n {
+        let a = points[i];
+        let edge = points[(i + 1) % n] - a;
+        let mut normal = Vector2::new(edge.y, -edge.x).normalize();
+        if (a - center).dot(normal) < 0.0 {
+            normal = -normal;
+        }
+
+        let distance = (a - origin).dot(normal);
+        let speed = dir.dot(normal);
+
+        if speed == 0.0 {
+            if distance < 0.0 {
+                return None;
+            }
+            continue;
+        }
+
+        let t = distance / speed;
+        if speed < 0.0 {
+            if t > t_enter {
+                t_enter = t;
+                enter_normal = normal;
+            }
+        }
+        else if t < t_exit {
+            t_exit = t;
+        }
+
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+
+    if t_enter < 0.0 {
+        return None;
+    }
+
+    Some((t_enter, enter_normal))
+}
+
+// separating axis test. returns the normal pointing from `a` towards `b` and the
+// penetration depth along it, or None if the shapes don't overlap. `axes` is
+// scratch space, passed in so it can be reused between calls
+pub fn collide(a: &Shape, b: &Shape, axes: &mut Vec<Vector2>) -> Option<(Vector2, f32)> {
+    axes.clear();
+    a.push_edge_normals(axes);
+    b.push_edge_normals(axes);
+
+    // rounded shapes can also be separated along the line between two core vertices
+    if a.radius > 0.0 || b.radius > 0.0 {
+        for pa in &a.points {
+            for pb in &b.points {
+                axes.push((*pb - *pa).normalize());
+            }
+        }
+    }
+
+    // two circles sharing a center have no axis between them at all. any direction
+    // separates them equally well, so they're pushed apart vertically
+    axes.retain(|axis| axis.dot(*axis) > 0.0);
+    if axes.is_empty() {
+        axes.push(Vector2::new(0.0, 1.0));
+    }
+
+    let mut best: Option<(Vector2, f32)> = None;
+
+    for &axis in axes.iter() {
+        let (min_a, max_a) = a.project(axis);
+        let (min_b, max_b) = b.project(axis);
+        let overlap = max_a.min(max_b) - min_a.max(min_b);
+
+        if overlap <= 0.0 {
+            return None;
+        }
+
+        if best.is_none_or(|(_, depth)| overlap < depth) {
+            best = Some((axis, overlap));
+        }
+    }
+
+    best.map(|(axis, depth)| {
+        if (b.center() - a.center()).dot(axis) < 0.0 {
+            (-axis, depth)
+        }
+        else {
+            (axis, depth)
+        }
+    })
+}
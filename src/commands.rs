@@ -0,0 +1,67 @@
+use crate::{
+    bundle::Bundle,
+    component::{Component, ComponentManager},
+    entity::{Entity, EntitySystem},
+    resource::Resource,
+};
+
+type Command = Box<dyn FnOnce(&mut EntitySystem, &mut ComponentManager)>;
+
+// structural changes recorded by systems while they're iterating and applied by
+// the `SystemManager` once the current stage is over. entities are handles, so a
+// command aimed at something that was despawned in the meantime does nothing
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+#[allow(dead_code)]
+impl Commands {
+    pub fn new() -> Self {
+        Self {
+            queue: Vec::new(),
+        }
+    }
+
+    pub fn spawn<B: Bundle + 'static>(&mut self, bundle: B) {
+        self.queue.push(Box::new(move |entity_system, component_manager| {
+            entity_system.spawn(bundle, component_manager);
+        }));
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.queue.push(Box::new(move |entity_system, component_manager| {
+            entity_system.despawn_entity(entity, component_manager);
+        }));
+    }
+
+    pub fn insert<T: Component + 'static>(&mut self, entity: Entity, component: T) {
+        self.queue.push(Box::new(move |entity_system, component_manager| {
+            entity_system.insert_component(entity, component, component_manager);
+        }));
+    }
+
+    pub fn remove<T: Component + 'static>(&mut self, entity: Entity) {
+        self.queue.push(Box::new(move |entity_system, component_manager| {
+            entity_system.remove_component::<T>(entity, component_manager);
+        }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    // runs the commands in the order they were recorded
+    pub fn apply(&mut self, entity_system: &mut EntitySystem, component_manager: &mut ComponentManager) {
+        for command in self.queue.drain(..) {
+            command(entity_system, component_manager);
+        }
+    }
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resource for Commands {}
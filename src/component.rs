@@ -5,50 +5,141 @@ use std::{
         Any
     },
 };
+
+use crate::{
+    bundle::Bundle,
+    query::{self, Fetch, QueryIter, ReadOnlyFetch},
+};
   
 pub trait Component {}
 
+pub trait ComponentStorage {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn remove_entity(&mut self, entity: usize) -> bool;
+}
+
+const PAGE_SIZE: usize = 256;
+const EMPTY: usize = usize::MAX;
+
+// sparse set: `components` and `entities` are dense and in the same order, so
+// iterating only ever touches entities that own the component. `sparse` maps an
+// entity back to its dense index and is split into pages that are only
+// allocated once an entity in their range gets the component
 #[derive(Clone)]
 pub struct ComponentList<T> {
-    pub components: Vec<T>,
-    pub entity_map: HashMap<usize, usize>
+    components: Vec<T>,
+    entities: Vec<usize>,
+    sparse: Vec<Option<Box<[usize; PAGE_SIZE]>>>,
 }
 
+#[allow(dead_code)]
 impl<T: Component + 'static> ComponentList<T> {
     pub fn new() -> Self {
         Self {
             components: Vec::<T>::new(),
-            entity_map: HashMap::new()
+            entities: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+
+    fn dense_index(&self, entity: usize) -> Option<usize> {
+        match self.sparse.get(entity / PAGE_SIZE) {
+            Some(Some(page)) if page[entity % PAGE_SIZE] != EMPTY => Some(page[entity % PAGE_SIZE]),
+            _ => None
+        }
+    }
+
+    fn set_dense_index(&mut self, entity: usize, index: usize) {
+        let page = entity / PAGE_SIZE;
+        if page >= self.sparse.len() {
+            self.sparse.resize_with(page + 1, || None);
         }
+
+        self.sparse[page].get_or_insert_with(|| Box::new([EMPTY; PAGE_SIZE]))[entity % PAGE_SIZE] = index;
+    }
+
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    // the owner of every component, in the same order as `iter`
+    pub fn entities(&self) -> &[usize] {
+        &self.entities
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.components.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.components.iter_mut()
+    }
+
+    pub fn contains(&self, entity: usize) -> bool {
+        self.dense_index(entity).is_some()
     }
 
     pub fn add_component(&mut self, entity: usize, component: T) {
         let index = self.components.len();
-        self.entity_map.insert(entity, index);
+        self.set_dense_index(entity, index);
+        self.entities.push(entity);
         self.components.push(component);
     }
 
     pub fn get_entity_component(&self, entity: usize) -> Option<&T> {
-        let index = self.entity_map.get(&entity);
-        match index {
-            Some(i) => Some(&self.components[*i]),
-            None => None
-        }
+        self.dense_index(entity).map(|i| &self.components[i])
     }
 
     pub fn get_entity_component_mut(&mut self, entity: usize) -> Option<&mut T> {
-        let index = self.entity_map.get(&entity);
-        match index {
-            Some(i) => Some(&mut self.components[*i]),
-            None => None
+        self.dense_index(entity).map(|i| &mut self.components[i])
+    }
+
+    pub fn remove_component(&mut self, entity: usize) -> Option<T> {
+        let index = self.dense_index(entity)?;
+        self.set_dense_index(entity, EMPTY);
+
+        // the last component is moved into the hole, so its owner has to be repointed
+        let last = self.components.len() - 1;
+        if index != last {
+            let moved_entity = self.entities[last];
+            self.set_dense_index(moved_entity, index);
         }
+
+        self.entities.swap_remove(index);
+        Some(self.components.swap_remove(index))
+    }
+}
+
+impl<T: Component + 'static> Default for ComponentList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Component + 'static> ComponentStorage for ComponentList<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_entity(&mut self, entity: usize) -> bool {
+        self.remove_component(entity).is_some()
     }
 }
 
 pub struct ComponentManager {
-    pub component_lists: HashMap<TypeId, Box<dyn Any>>
+    pub component_lists: HashMap<TypeId, Box<dyn ComponentStorage>>
 }
 
+#[allow(dead_code)]
 impl ComponentManager {  
     pub fn new() -> Self {
         Self {
@@ -56,15 +147,15 @@ impl ComponentManager {
         }
     }
 
-    pub fn register_components<T: 'static>(&mut self) where T : Component {
+    pub fn register_components<T: Component + 'static>(&mut self) {
         self.component_lists.insert(TypeId::of::<T>(), Box::new(ComponentList::<T>::new()));
     }
 
-    pub fn get_components<T: 'static>(&self) -> Option<&ComponentList<T>> where T : Component {
+    pub fn get_components<T: Component + 'static>(&self) -> Option<&ComponentList<T>> {
         let boxed_component_lists = self.component_lists.get(&TypeId::of::<T>());
         match boxed_component_lists {
             Some(b) => {
-                let component_lists = b.downcast_ref::<ComponentList<T>>();
+                let component_lists = b.as_any().downcast_ref::<ComponentList<T>>();
                 match component_lists {
                     Some(c) => Some(c),
                     None => None
@@ -74,11 +165,11 @@ impl ComponentManager {
         }
     }
 
-    pub fn get_components_mut<T: 'static>(&mut self) -> Option<&mut ComponentList<T>> where T : Component {
+    pub fn get_components_mut<T: Component + 'static>(&mut self) -> Option<&mut ComponentList<T>> {
         let boxed_component_lists = self.component_lists.get_mut(&TypeId::of::<T>());
         match boxed_component_lists {
             Some(b) => {
-                let component_lists = b.downcast_mut::<ComponentList<T>>();
+                let component_lists = b.as_any_mut().downcast_mut::<ComponentList<T>>();
                 match component_lists {
                     Some(c) => Some(c),
                     None => None
@@ -88,22 +179,122 @@ impl ComponentManager {
         }
     }
 
-    pub fn add_component<T: 'static>(&mut self, entity: usize) where T : Component + Default {
+    pub fn add_component<T: Component + Default + 'static>(&mut self, entity: usize) {
+        self.insert_component(entity, T::default());
+    }
+
+    pub fn insert_component<T: Component + 'static>(&mut self, entity: usize, component: T) {
         let component_lists = self.get_components_mut::<T>();
         match component_lists {
-            Some(c) => c.add_component(entity, T::default()),
+            Some(c) => match c.get_entity_component_mut(entity) {
+                Some(existing) => *existing = component,
+                None => c.add_component(entity, component)
+            },
             None => {
                 self.register_components::<T>();
-                self.add_component::<T>(entity);
+                self.insert_component(entity, component);
             }
         }
     }
 
-    pub fn entity_has_component<T: 'static>(&self, entity: usize) -> bool where T : Component {
+    pub fn insert_bundle<B>(&mut self, entity: usize, bundle: B) where B : Bundle {
+        bundle.insert(entity, self);
+    }
+
+    pub fn remove_component<T: Component + 'static>(&mut self, entity: usize) -> Option<T> {
+        self.get_components_mut::<T>()?.remove_component(entity)
+    }
+
+    pub fn remove_entity(&mut self, entity: usize) {
+        for component_list in self.component_lists.values_mut() {
+            component_list.remove_entity(entity);
+        }
+    }
+
+    pub fn query<'a, Q>(&'a self) -> QueryIter<'a, Q> where Q : Fetch<'a> + ReadOnlyFetch {
+        // read-only queries never write through the pointer
+        unsafe { QueryIter::new(self as *const Self as *mut Self) }
+    }
+
+    pub fn query_mut<'a, Q>(&'a mut self) -> QueryIter<'a, Q> where Q : Fetch<'a> {
+        query::check_access::<Q>();
+        unsafe { QueryIter::new(self as *mut Self) }
+    }
+
+    pub fn entity_has_component<T: Component + 'static>(&self, entity: usize) -> bool {
         let component_lists = self.get_components::<T>();
         match component_lists {
-            Some(c) => c.entity_map.contains_key(&entity),
+            Some(c) => c.contains(entity),
             None => false
         }
     }
 }
+
+impl Default for ComponentManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // remembers who it was added for, so lookups can be checked against the owner
+    struct Owner(usize);
+
+    impl Component for Owner {}
+
+    fn owner(list: &ComponentList<Owner>, entity: usize) -> Option<usize> {
+        list.get_entity_component(entity).map(|o| o.0)
+    }
+
+    fn list_of(entities: &[usize]) -> ComponentList<Owner> {
+        let mut list = ComponentList::new();
+        for entity in entities {
+            list.add_component(*entity, Owner(*entity));
+        }
+        list
+    }
+
+    #[test]
+    fn remove_then_lookup_across_page_boundaries() {
+        let mut list = list_of(&[0, PAGE_SIZE - 1, PAGE_SIZE, PAGE_SIZE * 2 - 1, PAGE_SIZE * 4]);
+
+        assert_eq!(list.remove_component(PAGE_SIZE).map(|o| o.0), Some(PAGE_SIZE));
+        assert_eq!(list.remove_component(PAGE_SIZE - 1).map(|o| o.0), Some(PAGE_SIZE - 1));
+
+        assert!(!list.contains(PAGE_SIZE));
+        assert!(!list.contains(PAGE_SIZE - 1));
+        for entity in [0, PAGE_SIZE * 2 - 1, PAGE_SIZE * 4] {
+            assert_eq!(owner(&list, entity), Some(entity));
+        }
+
+        // pages that were never allocated, or lie past the end, just miss
+        assert_eq!(owner(&list, PAGE_SIZE * 3), None);
+        assert_eq!(owner(&list, PAGE_SIZE * 100), None);
+        assert!(list.remove_component(PAGE_SIZE).is_none());
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn swap_remove_repoints_the_moved_entity() {
+        let mut list = list_of(&[10, PAGE_SIZE + 10, PAGE_SIZE * 3 + 10]);
+
+        // the last entity, on another page, takes the removed one's slot
+        list.remove_component(10);
+        assert_eq!(list.entities(), &[PAGE_SIZE * 3 + 10, PAGE_SIZE + 10]);
+        assert_eq!(owner(&list, PAGE_SIZE * 3 + 10), Some(PAGE_SIZE * 3 + 10));
+        assert_eq!(owner(&list, PAGE_SIZE + 10), Some(PAGE_SIZE + 10));
+
+        // removing the last entity moves nothing
+        list.remove_component(PAGE_SIZE + 10);
+        assert_eq!(list.entities(), &[PAGE_SIZE * 3 + 10]);
+        assert_eq!(owner(&list, PAGE_SIZE * 3 + 10), Some(PAGE_SIZE * 3 + 10));
+
+        // and the freed slots can be taken again
+        list.add_component(10, Owner(10));
+        assert_eq!(owner(&list, 10), Some(10));
+        assert_eq!(list.iter().map(|o| o.0).collect::<Vec<_>>(), list.entities());
+    }
+}
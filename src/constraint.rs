@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use ggez::{Context, GameResult, graphics::{self, Color}, mint::Point2};
+
+use crate::{
+    system::{System, Time},
+    resource::Resources,
+    commands::Commands,
+    component::{ComponentManager, Component},
+    entity::{Entity, EntitySystem},
+    transform::Transform,
+    rigidbody::Rigidbody,
+    gravity::Gravity,
+    vector2::Vector2,
+};
+
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+pub enum JointKind {
+    // keeps the two ends exactly `length` apart
+    Distance { length: f32 },
+    // pulls the two ends towards `rest_length`, force is stiffness * stretch
+    // minus damping * stretch speed
+    Spring { rest_length: f32, stiffness: f32, damping: f32 },
+    // holds the two ends together
+    Pin,
+    // only stops the ends from getting further than `max_length` apart, slack is free
+    Rope { max_length: f32 },
+}
+
+// joints live on their own entities so a body can take part in any number of them.
+// `b` of None attaches `a` to the fixed world point `anchor` instead of a second body.
+// a joint is despawned along with the first of its ends to go
+#[derive(Copy, Clone)]
+pub struct Joint {
+    pub a: Entity,
+    pub b: Option<Entity>,
+    pub anchor: Vector2,
+    pub kind: JointKind,
+}
+
+#[allow(dead_code)]
+impl Joint {
+    pub fn between(a: Entity, b: Entity, kind: JointKind) -> Self {
+        Self {
+            a,
+            b: Some(b),
+            anchor: Vector2::default(),
+            kind,
+        }
+    }
+
+    pub fn to_world(a: Entity, anchor: Vector2, kind: JointKind) -> Self {
+        Self {
+            a,
+            b: None,
+            anchor,
+            kind,
+        }
+    }
+}
+
+impl Component for Joint {}
+
+// spawns a hanging chain of `segments` bodies starting below `anchor`, each linked
+// to the previous one by a rope joint and the first one to the anchor itself.
+// returns the segments from the top down so callers can add sprites, colliders
+// or hang something off the last one
+#[allow(dead_code)]
+pub fn spawn_rope(
+    entity_system: &mut EntitySystem,
+    component_manager: &mut ComponentManager,
+    anchor: Vector2,
+    segments: usize,
+    segment_length: f32,
+    segment_mass: f32,
+) -> Vec<Entity> {
+    let mut chain: Vec<Entity> = Vec::with_capacity(segments);
+
+    for i in 0..segments {
+        let mut rigidbody = Rigidbody::default();
+        rigidbody.set_mass(segment_mass);
+
+        let pos = anchor + Vector2::new(0.0, segment_length * (i + 1) as f32);
+        let segment = entity_system.spawn((
+            Transform::new(pos),
+            rigidbody,
+            Gravity::default(),
+        ), component_manager);
+
+        let joint = match chain.last() {
+            Some(prev) => Joint::between(*prev, segment, JointKind::Rope { max_length: segment_length }),
+            None => Joint::to_world(segment, anchor, JointKind::Rope { max_length: segment_length }),
+        };
+        entity_system.spawn((joint,), component_manager);
+
+        chain.push(segment);
+    }
+
+    chain
+}
+
+const SOLVER_ITERATIONS: usize = 8;
+
+#[derive(Copy, Clone)]
+struct Point {
+    pos: Vector2,
+    vel: Vector2,
+    inv_mass: f32,
+}
+
+// runs after integration and before collision. springs act on velocities,
+// the rigid joints are solved iteratively on positions and the corrections
+// are fed back into the velocities so bodies keep swinging instead of drifting
+#[derive(Default)]
+pub struct ConstraintSystem {
+    joints: Vec<Joint>,
+    points: Vec<Point>,
+    lookup: HashMap<usize, usize>,
+    start: Vec<Vector2>,
+}
+
+impl ConstraintSystem {
+    // the two ends of a joint, with the world anchor standing in for a missing `b`
+    fn ends(&self, joint: &Joint) -> Option<(usize, Option<usize>)> {
+        let a = *self.lookup.get(&joint.a.index)?;
+        match joint.b {
+            Some(b) => Some((a, Some(*self.lookup.get(&b.index)?))),
+            None => Some((a, None)),
+        }
+    }
+
+    fn end(&self, b: Option<usize>, joint: &Joint) -> (Vector2, Vector2, f32) {
+        match b {
+            Some(b) => (self.points[b].pos, self.points[b].vel, self.points[b].inv_mass),
+            None => (joint.anchor, Vector2::default(), 0.0),
+        }
+    }
+
+    fn apply_springs(&mut self, delta_time: f32) {
+        for k in 0..self.joints.len() {
+            let joint = self.joints[k];
+            let JointKind::Spring { rest_length, stiffness, damping } = joint.kind else {
+                continue;
+            };
+            let Some((a, b)) = self.ends(&joint) else {
+                continue;
+            };
+
+            let (pos_b, vel_b, inv_mass_b) = self.end(b, &joint);
+            let delta = pos_b - self.points[a].pos;
+            let dir = delta.normalize();
+            let stretch = delta.length() - rest_length;
+            let stretch_speed = (vel_b - self.points[a].vel).dot(dir);
+
+            let impulse = dir * ((stiffness * stretch + damping * stretch_speed) * delta_time);
+            self.points[a].vel = self.points[a].vel + impulse * self.points[a].inv_mass;
+            if let Some(b) = b {
+                self.points[b].vel = self.points[b].vel - impulse * inv_mass_b;
+            }
+        }
+    }
+
+    fn solve_positions(&mut self) {
+        for _ in 0..SOLVER_ITERATIONS {
+            for k in 0..self.joints.len() {
+                let joint = self.joints[k];
+                let Some((a, b)) = self.ends(&joint) else {
+                    continue;
+                };
+
+                let (pos_b, _, inv_mass_b) = self.end(b, &joint);
+                let delta = pos_b - self.points[a].pos;
+                let distance = delta.length();
+
+                let error = match joint.kind {
+                    JointKind::Distance { length } => distance - length,
+                    JointKind::Pin => distance,
+                    JointKind::Rope { max_length } => (distance - max_length).max(0.0),
+                    JointKind::Spring { .. } => continue,
+                };
+
+                let inv_mass_sum = self.points[a].inv_mass + inv_mass_b;
+                if error == 0.0 || inv_mass_sum == 0.0 {
+                    continue;
+                }
+
+                let correction = delta.normalize() * (error / inv_mass_sum);
+                self.points[a].pos = self.points[a].pos + correction * self.points[a].inv_mass;
+                if let Some(b) = b {
+                    self.points[b].pos = self.points[b].pos - correction * inv_mass_b;
+                }
+            }
+        }
+    }
+}
+
+// whether both ends of the joint still refer to the entities it was made with
+fn ends_alive(entities: &EntitySystem, joint: &Joint) -> bool {
+    entities.is_alive(joint.a) && joint.b.is_none_or(|b| entities.is_alive(b))
+}
+
+impl System for ConstraintSystem {
+    fn update(&mut self, _: &mut Context, entities: &EntitySystem, component_manager: &mut ComponentManager, resources: &mut Resources) {
+        let delta_time = resources.get::<Time>().map_or(0.0, |t| t.delta);
+
+        self.joints.clear();
+        for (entity, joint) in component_manager.query::<&Joint>() {
+            if ends_alive(entities, joint) {
+                self.joints.push(*joint);
+            }
+            else if let Some(entity) = entities.entity(entity) {
+                resources.get_or_insert_default::<Commands>().despawn(entity);
+            }
+        }
+        if self.joints.is_empty() {
+            return;
+        }
+
+        self.points.clear();
+        self.lookup.clear();
+        for (entity, (transform, rigidbody)) in component_manager.query::<(&Transform, &Rigidbody)>() {
+            self.lookup.insert(entity, self.points.len());
+            self.points.push(Point {
+                pos: transform.pos,
+                vel: rigidbody.vel,
+                inv_mass: rigidbody.effective_inv_mass(),
+            });
+        }
+
+        self.apply_springs(delta_time);
+
+        self.start.clear();
+        self.start.extend(self.points.iter().map(|p| p.pos));
+        self.solve_positions();
+
+        for (entity, (transform, rigidbody)) in component_manager.query_mut::<(&mut Transform, &mut Rigidbody)>() {
+            if let Some(i) = self.lookup.get(&entity) {
+                let point = self.points[*i];
+                if delta_time > 0.0 {
+                    rigidbody.vel = point.vel + (point.pos - self.start[*i]) * (1.0 / delta_time);
+                }
+                transform.pos = point.pos;
+            }
+        }
+    }
+
+    fn draw(&self, ctx: &mut Context, entities: &EntitySystem, component_manager: &ComponentManager, resources: &Resources) -> GameResult {
+        let alpha = resources.get::<Time>().map_or(1.0, |t| t.alpha);
+
+        let Some(transforms) = component_manager.get_components::<Transform>() else {
+            return Ok(());
+        };
+
+        let lerped = |entity: Entity| transforms
+            .get_entity_component(entity.index)
+            .map(|t| Vector2::default().lerp(t.prev_pos, t.pos, alpha));
+
+        for (_, joint) in component_manager.query::<&Joint>() {
+            if !ends_alive(entities, joint) {
+                continue;
+            }
+
+            let Some(a) = lerped(joint.a) else {
+                continue;
+            };
+            let b = match joint.b {
+                Some(b) => match lerped(b) {
+                    Some(b) => b,
+                    None => continue,
+                },
+                None => joint.anchor,
+            };
+
+            if (b - a).dot(b - a) <= f32::EPSILON {
+                continue;
+            }
+
+            let line = graphics::Mesh::new_line(
+                ctx,
+                &[Point2 { x: a.x, y: a.y }, Point2 { x: b.x, y: b.y }],
+                2.0,
+                Color::WHITE
+            )?;
+            graphics::draw(ctx, &line, graphics::DrawParam::default())?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,19 +1,186 @@
-pub struct Entity(pub usize);
+use crate::{
+  bundle::Bundle,
+  component::{Component, ComponentManager},
+};
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Entity {
+  pub index: usize,
+  pub generation: u32,
+}
 
 pub struct EntitySystem {
-  pub entities: usize
+  pub entities: usize,
+  generations: Vec<u32>,
+  alive: Vec<bool>,
+  free: Vec<usize>,
 }
 
+#[allow(dead_code)]
 impl EntitySystem {
   pub fn new() -> Self {
     Self {
       entities: 0,
+      generations: Vec::new(),
+      alive: Vec::new(),
+      free: Vec::new(),
     }
   }
 
   pub fn create_entity(&mut self) -> Entity {
-    let new_entity_id = self.entities;
-    self.entities += 1;
-    Entity(new_entity_id)
+    match self.free.pop() {
+      Some(index) => {
+        self.alive[index] = true;
+        Entity {
+          index,
+          generation: self.generations[index],
+        }
+      },
+      None => {
+        let index = self.entities;
+        self.entities += 1;
+        self.generations.push(0);
+        self.alive.push(true);
+        Entity {
+          index,
+          generation: 0,
+        }
+      }
+    }
+  }
+
+  pub fn spawn<B>(&mut self, bundle: B, component_manager: &mut ComponentManager) -> Entity where B: Bundle {
+    let entity = self.create_entity();
+    component_manager.insert_bundle(entity.index, bundle);
+    entity
+  }
+
+  // the live handle for an index, if anything is using it
+  pub fn entity(&self, index: usize) -> Option<Entity> {
+    if index < self.entities && self.alive[index] {
+      Some(Entity {
+        index,
+        generation: self.generations[index],
+      })
+    }
+    else {
+      None
+    }
+  }
+
+  pub fn is_alive(&self, entity: Entity) -> bool {
+    entity.index < self.entities &&
+    self.alive[entity.index] &&
+    self.generations[entity.index] == entity.generation
+  }
+
+  // strips every component from the entity and frees its index for reuse,
+  // bumping the generation so any handle still pointing at it goes stale
+  pub fn despawn_entity(&mut self, entity: Entity, component_manager: &mut ComponentManager) -> bool {
+    if !self.is_alive(entity) {
+      return false;
+    }
+
+    component_manager.remove_entity(entity.index);
+
+    self.alive[entity.index] = false;
+    self.generations[entity.index] = self.generations[entity.index].wrapping_add(1);
+    self.free.push(entity.index);
+    true
+  }
+
+  // the `ComponentManager` works on bare indices, which get reused once an entity
+  // is despawned. these go through a handle instead and do nothing when it's stale
+  pub fn insert_component<T: Component + 'static>(&self, entity: Entity, component: T, component_manager: &mut ComponentManager) -> bool {
+    if !self.is_alive(entity) {
+      return false;
+    }
+
+    component_manager.insert_component(entity.index, component);
+    true
   }
-}
\ No newline at end of file
+
+  pub fn remove_component<T: Component + 'static>(&self, entity: Entity, component_manager: &mut ComponentManager) -> Option<T> {
+    if !self.is_alive(entity) {
+      return None;
+    }
+
+    component_manager.remove_component::<T>(entity.index)
+  }
+
+  pub fn get_component<'a, T: Component + 'static>(&self, entity: Entity, component_manager: &'a ComponentManager) -> Option<&'a T> {
+    if !self.is_alive(entity) {
+      return None;
+    }
+
+    component_manager.get_components::<T>()?.get_entity_component(entity.index)
+  }
+
+  pub fn get_component_mut<'a, T: Component + 'static>(&self, entity: Entity, component_manager: &'a mut ComponentManager) -> Option<&'a mut T> {
+    if !self.is_alive(entity) {
+      return None;
+    }
+
+    component_manager.get_components_mut::<T>()?.get_entity_component_mut(entity.index)
+  }
+}
+
+impl Default for EntitySystem {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Debug, PartialEq)]
+  struct Hp(u32);
+
+  impl Component for Hp {}
+
+  #[test]
+  fn despawned_index_is_reused_with_a_new_generation() {
+    let mut entities = EntitySystem::new();
+    let mut component_manager = ComponentManager::new();
+
+    let a = entities.spawn((Hp(1),), &mut component_manager);
+    let b = entities.create_entity();
+    assert!(entities.despawn_entity(a, &mut component_manager));
+
+    let c = entities.create_entity();
+    assert_eq!(c.index, a.index);
+    assert_ne!(c.generation, a.generation);
+    assert_eq!(entities.entity(a.index), Some(c));
+    assert!(entities.is_alive(b));
+
+    // the reused index starts out without the old entity's components
+    assert!(!component_manager.entity_has_component::<Hp>(c.index));
+  }
+
+  #[test]
+  fn stale_handle_is_rejected() {
+    let mut entities = EntitySystem::new();
+    let mut component_manager = ComponentManager::new();
+
+    let stale = entities.spawn((Hp(1),), &mut component_manager);
+    entities.despawn_entity(stale, &mut component_manager);
+    let owner = entities.spawn((Hp(2),), &mut component_manager);
+    assert_eq!(owner.index, stale.index);
+
+    assert!(!entities.is_alive(stale));
+    assert!(!entities.insert_component(stale, Hp(3), &mut component_manager));
+    assert!(entities.get_component::<Hp>(stale, &component_manager).is_none());
+    assert!(entities.get_component_mut::<Hp>(stale, &mut component_manager).is_none());
+    assert!(entities.remove_component::<Hp>(stale, &mut component_manager).is_none());
+    assert!(!entities.despawn_entity(stale, &mut component_manager));
+
+    // none of that reached the entity that owns the index now
+    assert!(entities.is_alive(owner));
+    assert_eq!(entities.get_component::<Hp>(owner, &component_manager), Some(&Hp(2)));
+
+    assert!(entities.insert_component(owner, Hp(4), &mut component_manager));
+    assert_eq!(entities.remove_component::<Hp>(owner, &mut component_manager), Some(Hp(4)));
+  }
+}
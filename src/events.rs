@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    any::{
+        TypeId,
+        Any
+    },
+};
+
+use crate::resource::Resource;
+
+pub trait Event {}
+
+trait EventStorage {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn update(&mut self);
+}
+
+// events live for two simulation steps: the step they were sent in and the next
+// one, so a reader that runs before the sender in a step still gets to see them.
+// every event gets a sequential id and readers only remember the next id they
+// haven't seen yet
+struct EventChannel<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+    // id of the first event in `previous`
+    start: usize,
+}
+
+impl<T> EventChannel<T> {
+    fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            start: 0,
+        }
+    }
+
+    fn end(&self) -> usize {
+        self.start + self.previous.len() + self.current.len()
+    }
+
+    fn since(&self, id: usize) -> impl Iterator<Item = &T> {
+        let skip = id.saturating_sub(self.start);
+        self.previous.iter().chain(self.current.iter()).skip(skip)
+    }
+}
+
+impl<T: Event + 'static> EventStorage for EventChannel<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn update(&mut self) {
+        self.start += self.previous.len();
+        self.previous.clear();
+        std::mem::swap(&mut self.previous, &mut self.current);
+    }
+}
+
+// used for reads made from outside of any system, e.g. from `main.rs`
+pub const NO_READER: usize = usize::MAX;
+
+// typed event channels, stored as a resource. the `SystemManager` tells it which
+// system is running so every system keeps its own read cursor per event type
+// and sees each event exactly once
+pub struct Events {
+    channels: HashMap<TypeId, Box<dyn EventStorage>>,
+    cursors: HashMap<(TypeId, usize), usize>,
+    reader: usize,
+}
+
+#[allow(dead_code)]
+impl Events {
+    pub fn new() -> Self {
+        Self {
+            channels: HashMap::new(),
+            cursors: HashMap::new(),
+            reader: NO_READER,
+        }
+    }
+
+    fn channel<T: Event + 'static>(&self) -> Option<&EventChannel<T>> {
+        self.channels
+            .get(&TypeId::of::<T>())
+            .and_then(|c| c.as_any().downcast_ref::<EventChannel<T>>())
+    }
+
+    fn channel_mut<T: Event + 'static>(&mut self) -> &mut EventChannel<T> {
+        self.channels
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(EventChannel::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<EventChannel<T>>()
+            .expect("event channel stored under the wrong type")
+    }
+
+    pub fn send<T: Event + 'static>(&mut self, event: T) {
+        self.channel_mut::<T>().current.push(event);
+    }
+
+    // events of this type sent since the current reader last called `read`
+    pub fn read<T: Event + 'static>(&mut self) -> impl Iterator<Item = &T> {
+        let key = (TypeId::of::<T>(), self.reader);
+        let channel = self.channel_mut::<T>();
+        let end = channel.end();
+
+        let cursor = self.cursors.insert(key, end).unwrap_or(0);
+        self.channel::<T>().into_iter().flat_map(move |c| c.since(cursor))
+    }
+
+    // every event of this type still buffered, without moving any cursor.
+    // for `System::draw`, which only gets read access
+    pub fn iter<T: Event + 'static>(&self) -> impl Iterator<Item = &T> {
+        self.channel::<T>().into_iter().flat_map(|c| c.since(0))
+    }
+
+    pub fn set_reader(&mut self, reader: usize) {
+        self.reader = reader;
+    }
+
+    // called after every simulation step by the `SystemManager`, drops events that
+    // are two steps old
+    pub fn update(&mut self) {
+        for channel in self.channels.values_mut() {
+            channel.update();
+        }
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resource for Events {}
@@ -1,14 +1,70 @@
-use crate::component::Component;
+use crate::{
+    component::Component,
+    resource::Resource,
+    vector2::Vector2,
+};
 
+// world gravity in units/s². y points down the screen, so positive y falls
+pub const DEFAULT_GRAVITY: Vector2 = Vector2 { x: 0.0, y: 980.0 };
+
+// the gravity every body is under unless a `GravityZone` says otherwise
 #[derive(Copy, Clone)]
-pub struct Gravity(pub f32);
+pub struct WorldGravity {
+    pub gravity: Vector2,
+}
+
+impl Default for WorldGravity {
+    fn default() -> Self {
+      Self {
+        gravity: DEFAULT_GRAVITY,
+      }
+    }
+}
+
+impl Resource for WorldGravity {}
+
+// opts a body into gravity, scaled per entity
+#[derive(Copy, Clone)]
+pub struct Gravity {
+    pub scale: f32,
+}
 
 impl Default for Gravity {
     fn default() -> Self {
-      Self(0.0)
+      Self {
+        scale: 1.0,
+      }
     }  
 }
   
 
 impl Component for Gravity {}
-  
\ No newline at end of file
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ZoneMode {
+    // replaces the world gravity for bodies inside the zone
+    Override,
+    // added on top, e.g. wind or updrafts
+    Add,
+}
+
+// changes gravity for bodies whose position is inside the entity's `AABB`.
+// that `AABB` should be a trigger, otherwise it blocks bodies like any wall
+// and nothing ever gets inside
+#[derive(Copy, Clone)]
+pub struct GravityZone {
+    pub gravity: Vector2,
+    pub mode: ZoneMode,
+}
+
+impl Default for GravityZone {
+    fn default() -> Self {
+      Self {
+        gravity: Vector2::default(),
+        mode: ZoneMode::Add,
+      }
+    }
+}
+
+impl Component for GravityZone {}
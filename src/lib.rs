@@ -0,0 +1,23 @@
+pub mod vector2;
+pub mod entity;
+pub mod system;
+pub mod component;
+pub mod bundle;
+pub mod query;
+pub mod physics;
+pub mod transform;
+pub mod rigidbody;
+pub mod gravity;
+pub mod movement;
+pub mod platformer;
+pub mod sprite;
+pub mod aabb;
+pub mod bounds;
+pub mod collider;
+pub mod raycast;
+pub mod broadphase;
+pub mod resource;
+pub mod events;
+pub mod commands;
+pub mod constraint;
+pub mod platform;
@@ -1,24 +1,18 @@
-mod vector2;
-mod entity;
-mod system;
-mod component;
-mod physics;
-mod transform;
-mod rigidbody;
-mod gravity;
-mod movement;
-mod sprite;
-mod aabb;
-
-use aabb::{CollisionSystem, AABB};
-use gravity::Gravity;
-use movement::{MovementSystem, Movement};
-use physics::PhysicsSystem;
-use rand::{thread_rng, Rng};
-use rigidbody::Rigidbody;
-use sprite::{Sprite, RenderSystem};
-use system::SystemManager;
-use component::ComponentManager;
+use ggez_test::aabb::{CollisionSystem, AABB, ContactFlags, LAYER_ALL};
+use ggez_test::collider::Collider;
+use ggez_test::constraint::{ConstraintSystem, spawn_rope};
+use ggez_test::gravity::{Gravity, GravityZone, WorldGravity, ZoneMode};
+use ggez_test::movement::MovementSystem;
+use ggez_test::platformer::{PlatformerSystem, PlatformerController};
+use ggez_test::physics::PhysicsSystem;
+use ggez_test::bounds::{WorldBounds, BoundsBehavior};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use ggez_test::platform::{MovingPlatform, MovingPlatformSystem};
+use ggez_test::rigidbody::{Rigidbody, BodyType};
+use ggez_test::sprite::{Sprite, RenderSystem};
+use ggez_test::system::{SystemManager, SystemConfig, Stage};
+use ggez_test::component::ComponentManager;
+use ggez_test::resource::Resources;
 use ggez::{
     graphics,
     graphics::Color,
@@ -29,68 +23,147 @@ use ggez::{
     ContextBuilder,
     event,
 };
-use transform::Transform;
-use vector2::Vector2;
+use ggez_test::transform::Transform;
+use ggez_test::vector2::Vector2;
+
+const WORLD_WIDTH: f32 = 1920.0;
+const WORLD_HEIGHT: f32 = 1080.0;
+
+const LAYER_PLAYER: u32 = 1 << 1;
+const LAYER_WALL: u32 = 1 << 2;
 
 struct State {
     system_manager: SystemManager,
     component_manager: ComponentManager,
+    resources: Resources,
 }
 
 impl State {
-    fn new(ctx: &Context) -> Self {
+    fn new() -> GameResult<Self> {
         let mut s = Self {
             system_manager: SystemManager::new(),
             component_manager: ComponentManager::new(),
+            resources: Resources::new(),
         };
 
-        s.system_manager.register_system::<PhysicsSystem>();
-        s.system_manager.register_system::<MovementSystem>();
-        s.system_manager.register_system::<RenderSystem>();
-        s.system_manager.register_system::<CollisionSystem>();
-
-        s.system_manager.entity_system.create_entity();
-
-        s.component_manager.add_component::<Transform>(0);
-        s.component_manager.add_component::<Rigidbody>(0);
-        s.component_manager.add_component::<Gravity>(0);
-        s.component_manager.add_component::<Movement>(0);
-        s.component_manager.add_component::<Sprite>(0);
-        s.component_manager.add_component::<AABB>(0);
-
-        s.component_manager.get_components_mut::<Transform>().unwrap().get_entity_component_mut(0).unwrap().pos = Vector2 { x: 64.0, y: 64.0 };
-        s.component_manager.get_components_mut::<Sprite>().unwrap().get_entity_component_mut(0).unwrap().color = Color::YELLOW;
-
-        let (screen_w, screen_h) = graphics::drawable_size(ctx);
-
-        for e in 1..15 {
-            s.system_manager.entity_system.create_entity();
-
-            let mut rng = thread_rng();
-            let x: f32 = rng.gen_range(0.0..screen_w);
-            let y: f32 = rng.gen_range(0.0..screen_h);
-    
-            s.component_manager.add_component::<Transform>(e);
-            s.component_manager.add_component::<Sprite>(e);
-            s.component_manager.add_component::<AABB>(e);
+        s.resources.insert(WorldBounds::new(
+            Vector2::default(),
+            Vector2::new(WORLD_WIDTH, WORLD_HEIGHT),
+            BoundsBehavior::Clamp,
+        ));
+        s.resources.insert(WorldGravity::default());
+        s.resources.insert(StdRng::from_entropy());
+
+        s.system_manager.set_fixed_timestep(60.0, 5);
+
+        s.system_manager.register_system_with::<MovementSystem>(
+            SystemConfig::new(Stage::Update).before::<PhysicsSystem>()
+        )?;
+        s.system_manager.register_system_with::<PlatformerSystem>(
+            SystemConfig::new(Stage::Update).before::<PhysicsSystem>()
+        )?;
+        s.system_manager.register_system_with::<MovingPlatformSystem>(
+            SystemConfig::new(Stage::Update).before::<PhysicsSystem>()
+        )?;
+        s.system_manager.register_system_with::<PhysicsSystem>(SystemConfig::new(Stage::Update))?;
+        s.system_manager.register_system_with::<ConstraintSystem>(
+            SystemConfig::new(Stage::Update).after::<PhysicsSystem>().before::<CollisionSystem>()
+        )?;
+        s.system_manager.register_system_with::<CollisionSystem>(
+            SystemConfig::new(Stage::Update).after::<PhysicsSystem>()
+        )?;
+        s.system_manager.register_system_with::<RenderSystem>(SystemConfig::new(Stage::Render))?;
+
+        s.system_manager.entity_system.spawn((
+            Transform::new(Vector2::new(64.0, 64.0)),
+            Rigidbody::default(),
+            Gravity::default(),
+            PlatformerController::default(),
+            ContactFlags::default(),
+            Sprite { color: Color::YELLOW, ..Sprite::default() },
+            AABB { layer: LAYER_PLAYER, mask: LAYER_WALL, ..AABB::default() },
+        ), &mut s.component_manager);
+
+        s.system_manager.entity_system.spawn((
+            Transform::new(Vector2::new(WORLD_WIDTH * 0.5, WORLD_HEIGHT - 16.0)),
+            AABB { w: WORLD_WIDTH, h: 32.0, layer: LAYER_WALL, mask: LAYER_ALL, ..AABB::default() },
+        ), &mut s.component_manager);
+
+        for _ in 1..15 {
+            let rng = s.resources.get_mut::<StdRng>().unwrap();
+            let x: f32 = rng.gen_range(0.0..WORLD_WIDTH);
+            let y: f32 = rng.gen_range(0.0..WORLD_HEIGHT);
+
+            s.system_manager.entity_system.spawn((
+                Transform::new(Vector2::new(x, y)),
+                Sprite { w: 96, h: 16, ..Sprite::default() },
+                AABB { w: 96.0, h: 16.0, layer: LAYER_WALL, mask: LAYER_ALL, one_way: true, ..AABB::default() },
+            ), &mut s.component_manager);
+        }
 
-            s.component_manager.get_components_mut::<Transform>().unwrap().get_entity_component_mut(e).unwrap().set_pos(Vector2 { x, y });
+        s.system_manager.entity_system.spawn((
+            Transform::new(Vector2::new(WORLD_WIDTH * 0.5, WORLD_HEIGHT - 96.0)),
+            AABB { layer: LAYER_WALL, mask: LAYER_ALL, ..AABB::default() },
+            Collider::Polygon {
+                points: vec![
+                    Vector2::new(-128.0, 64.0),
+                    Vector2::new(128.0, 64.0),
+                    Vector2::new(128.0, -64.0),
+                ],
+            },
+        ), &mut s.component_manager);
+
+        // updraft along the right edge, pushing up harder than gravity pulls down
+        s.system_manager.entity_system.spawn((
+            Transform::new(Vector2::new(WORLD_WIDTH - 96.0, WORLD_HEIGHT * 0.5)),
+            GravityZone { gravity: Vector2::new(0.0, -1400.0), mode: ZoneMode::Add },
+            AABB { w: 192.0, h: WORLD_HEIGHT, is_trigger: true, ..AABB::default() },
+        ), &mut s.component_manager);
+
+        // lift going up and down next to the slope
+        let lift_bottom = Vector2::new(WORLD_WIDTH * 0.75, WORLD_HEIGHT - 48.0);
+        s.system_manager.entity_system.spawn((
+            Transform::new(lift_bottom),
+            Rigidbody { body_type: BodyType::Kinematic, ..Rigidbody::default() },
+            MovingPlatform::new(vec![lift_bottom - Vector2::new(0.0, 480.0), lift_bottom], 160.0),
+            Sprite { w: 128, h: 16, color: Color::CYAN },
+            AABB { w: 128.0, h: 16.0, layer: LAYER_WALL, mask: LAYER_ALL, ..AABB::default() },
+        ), &mut s.component_manager);
+
+        // swinging hazard: a rope hanging from the ceiling with a heavy weight on the end
+        let rope = spawn_rope(
+            &mut s.system_manager.entity_system,
+            &mut s.component_manager,
+            Vector2::new(WORLD_WIDTH * 0.25, 0.0),
+            10,
+            24.0,
+            0.2,
+        );
+        for segment in &rope {
+            s.system_manager.entity_system.insert_component(*segment, Sprite { w: 6, h: 6, ..Sprite::default() }, &mut s.component_manager);
+        }
+        if let Some(weight) = rope.last() {
+            let mut rigidbody = Rigidbody::default();
+            rigidbody.set_mass(5.0).apply_impulse(Vector2::new(2000.0, 0.0));
+            let entities = &s.system_manager.entity_system;
+            entities.insert_component(*weight, rigidbody, &mut s.component_manager);
+            entities.insert_component(*weight, Sprite { color: Color::RED, ..Sprite::default() }, &mut s.component_manager);
         }
 
-        s
+        Ok(s)
     }
 }
 
 impl ggez::event::EventHandler<GameError> for State {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
-        self.system_manager.update(ctx, &mut self.component_manager);
+        self.system_manager.update(ctx, &mut self.component_manager, &mut self.resources);
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         graphics::clear(ctx, Color::BLACK);
 
-        self.system_manager.draw(ctx, &self.component_manager)?;
+        self.system_manager.draw(ctx, &self.component_manager, &self.resources)?;
 
         graphics::present(ctx)?;
         Ok(())
@@ -109,7 +182,7 @@ fn main() {
         .build()
         .unwrap();
 
-    let state = State::new(&ctx);
+    let state = State::new().unwrap();
 
     event::run(ctx, event_loop, state);
 }
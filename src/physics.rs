@@ -1,66 +1,123 @@
-use ggez::{Context, GameResult, graphics};
+use ggez::{Context, GameResult};
 
 use crate::{
     system::System,
     vector2::Vector2,
     component::ComponentManager,
+    entity::{Entity, EntitySystem},
+    resource::Resources,
+    system::Time,
     transform::Transform,
-    rigidbody::Rigidbody,
-    gravity::Gravity,
+    rigidbody::{Rigidbody, BodyType},
+    gravity::{Gravity, GravityZone, ZoneMode, WorldGravity},
+    aabb::AABB,
+    bounds::WorldBounds,
+    commands::Commands,
 };
 
-pub struct PhysicsSystem {}
+// reads the `WorldBounds` and `WorldGravity` resources, falling back to
+// their defaults when they haven't been inserted
+#[derive(Default)]
+pub struct PhysicsSystem {
+    zones: Vec<(AABB, GravityZone)>,
+    killed: Vec<Entity>,
+}
+
+impl PhysicsSystem {
+    // zone boxes are only moved onto their transform by the `CollisionSystem`,
+    // which runs after this one, so they're centered here the same way
+    fn gather_zones(&mut self, component_manager: &ComponentManager) {
+        self.zones.clear();
+        self.zones.extend(component_manager.query::<(Option<&Transform>, &AABB, &GravityZone)>().map(|(_, (transform, aabb, zone))| {
+            (transform.map_or(*aabb, |t| aabb.centered_at(t.pos)), *zone)
+        }));
+    }
+
+    fn gravity_at(&self, world_gravity: Vector2, pos: Vector2) -> Vector2 {
+        let inside = || self.zones.iter().filter(move |(aabb, _)| {
+            pos.x >= aabb.x && pos.x <= aabb.x + aabb.w &&
+            pos.y >= aabb.y && pos.y <= aabb.y + aabb.h
+        });
+
+        let mut gravity = inside()
+            .find(|(_, zone)| zone.mode == ZoneMode::Override)
+            .map_or(world_gravity, |(_, zone)| zone.gravity);
+
+        for (_, zone) in inside().filter(|(_, zone)| zone.mode == ZoneMode::Add) {
+            gravity = gravity + zone.gravity;
+        }
 
-impl Default for PhysicsSystem {
-    fn default() -> Self {
-        Self {}    
-    }  
+        gravity
+    }
 }
 
 impl System for PhysicsSystem {
-    fn update(&mut self, ctx: &mut Context, entities: usize, component_manager: &mut ComponentManager) {
-        for entity in 0..entities {
-            let has_transform_component = component_manager.entity_has_component::<Transform>(entity);
-            let has_rigidbody_component = component_manager.entity_has_component::<Rigidbody>(entity);
-            let has_gravity_component = component_manager.entity_has_component::<Gravity>(entity);
-
-            if has_transform_component && has_rigidbody_component && has_gravity_component {
-                let transform_list = component_manager.get_components::<Transform>().unwrap();
-                let transform = *transform_list.get_entity_component(entity).unwrap();
-
-                let rigidbody_list = component_manager.get_components::<Rigidbody>().unwrap();
-                let rigidbody = *rigidbody_list.get_entity_component(entity).unwrap();
-
-                let gravity_list = component_manager.get_components::<Gravity>().unwrap();
-                let gravity = *gravity_list.get_entity_component(entity).unwrap();
-
-                let new_velocity = Vector2::new(
-                    rigidbody.vel.x,
-                    rigidbody.vel.y - gravity.0,
-                );
-
-                let (screen_w, screen_h) = graphics::drawable_size(ctx);
-
-                let new_position = Vector2::new(
-                    transform.pos.x + rigidbody.vel.x,
-                    transform.pos.y + rigidbody.vel.y
-                ).clamp(
-                    Vector2::default(), 
-                    Vector2::new(screen_w, screen_h)
-                );
-
-                let rigidbody_list_mut = component_manager.get_components_mut::<Rigidbody>().unwrap();
-                let rigidbody_ref = rigidbody_list_mut.get_entity_component_mut(entity).unwrap();
-                rigidbody_ref.vel = new_velocity;
-
-                let transform_list_mut = component_manager.get_components_mut::<Transform>().unwrap();
-                let transform_ref = transform_list_mut.get_entity_component_mut(entity).unwrap();
-                transform_ref.pos = new_position;
+    fn update(&mut self, _: &mut Context, entities: &EntitySystem, component_manager: &mut ComponentManager, resources: &mut Resources) {
+        let delta_time = resources.get::<Time>().map_or(0.0, |t| t.delta);
+        let bounds = resources.get::<WorldBounds>().copied().unwrap_or_default();
+        let world_gravity = resources.get::<WorldGravity>().copied().unwrap_or_default().gravity;
+
+        self.killed.clear();
+
+        self.gather_zones(component_manager);
+
+        for (entity, (transform, rigidbody, gravity)) in component_manager.query_mut::<(&mut Transform, &mut Rigidbody, Option<&Gravity>)>() {
+            match rigidbody.body_type {
+                BodyType::Static => {
+                    rigidbody.vel = Vector2::default();
+                    rigidbody.accel = Vector2::default();
+                    continue;
+                },
+                BodyType::Kinematic => {},
+                BodyType::Dynamic => {
+                    let gravity = gravity.map_or(Vector2::default(), |g| self.gravity_at(world_gravity, transform.pos) * g.scale);
+
+                    // semi-implicit euler: velocity first, then position with the new velocity
+                    let accel = rigidbody.accel + gravity;
+                    rigidbody.vel = rigidbody.vel + accel * delta_time;
+                }
+            }
+
+            transform.pos = transform.pos + rigidbody.vel * delta_time;
+            rigidbody.accel = Vector2::default();
+
+            let (wrap_offset, killed) = bounds.apply(&mut transform.pos, &mut rigidbody.vel);
+            transform.prev_pos = transform.prev_pos + wrap_offset;
+            if killed {
+                self.killed.extend(entities.entity(entity));
             }
         }
+
+        let commands = resources.get_or_insert_default::<Commands>();
+        for entity in &self.killed {
+            commands.despawn(*entity);
+        }
     }
 
-    fn draw(&self, _: &mut Context, _: usize, _: &ComponentManager) -> GameResult {
+    fn draw(&self, _: &mut Context, _: &EntitySystem, _: &ComponentManager, _: &Resources) -> GameResult {
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zone_applies_around_its_transform_before_any_collision_step() {
+        let mut component_manager = ComponentManager::new();
+        // the box still sits at the origin, where it was created
+        component_manager.insert_bundle(0, (
+            Transform::new(Vector2::new(500.0, 500.0)),
+            AABB { w: 100.0, h: 100.0, is_trigger: true, ..AABB::default() },
+            GravityZone { gravity: Vector2::new(0.0, -100.0), mode: ZoneMode::Override },
+        ));
+
+        let mut system = PhysicsSystem::default();
+        system.gather_zones(&component_manager);
+
+        let world = Vector2::new(0.0, 980.0);
+        assert_eq!(system.gravity_at(world, Vector2::new(520.0, 480.0)).y, -100.0);
+        assert_eq!(system.gravity_at(world, Vector2::new(20.0, 20.0)).y, 980.0);
+    }
+}
@@ -0,0 +1,71 @@
+use ggez::{Context, GameResult};
+
+use crate::{
+    system::{System, Time},
+    resource::Resources,
+    component::{ComponentManager, Component},
+    entity::EntitySystem,
+    transform::Transform,
+    rigidbody::Rigidbody,
+    vector2::Vector2,
+};
+
+// drives a kinematic `Rigidbody` back and forth through `waypoints` at `speed`.
+// `CollisionSystem` carries whatever is standing on it and crushes what it
+// squeezes against a wall
+#[derive(Clone)]
+pub struct MovingPlatform {
+    pub waypoints: Vec<Vector2>,
+    pub speed: f32,
+    target: usize,
+}
+
+impl MovingPlatform {
+    pub fn new(waypoints: Vec<Vector2>, speed: f32) -> Self {
+        Self {
+            waypoints,
+            speed,
+            target: 0,
+        }
+    }
+}
+
+impl Component for MovingPlatform {}
+
+// close enough to a waypoint to move on to the next one
+const ARRIVE_DISTANCE: f32 = 0.5;
+
+#[derive(Default)]
+pub struct MovingPlatformSystem {}
+
+impl System for MovingPlatformSystem {
+    fn update(&mut self, _: &mut Context, _: &EntitySystem, component_manager: &mut ComponentManager, resources: &mut Resources) {
+        let delta_time = resources.get::<Time>().map_or(0.0, |t| t.delta);
+
+        if delta_time <= 0.0 {
+            return;
+        }
+
+        for (_, (transform, rigidbody, platform)) in component_manager.query_mut::<(&Transform, &mut Rigidbody, &mut MovingPlatform)>() {
+            if platform.waypoints.is_empty() {
+                rigidbody.vel = Vector2::default();
+                continue;
+            }
+
+            let mut to_target = platform.waypoints[platform.target] - transform.pos;
+            if to_target.length() <= ARRIVE_DISTANCE {
+                platform.target = (platform.target + 1) % platform.waypoints.len();
+                to_target = platform.waypoints[platform.target] - transform.pos;
+            }
+
+            // never overshoot the waypoint within a single step
+            let distance = to_target.length();
+            let speed = platform.speed.min(distance / delta_time);
+            rigidbody.vel = to_target.normalize() * speed;
+        }
+    }
+
+    fn draw(&self, _: &mut Context, _: &EntitySystem, _: &ComponentManager, _: &Resources) -> GameResult {
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use ggez::{Context, input::keyboard, event::KeyCode, GameResult};
+
+use crate::{
+    system::{System, Time},
+    resource::Resources,
+    component::{ComponentManager, Component},
+    entity::EntitySystem,
+    rigidbody::Rigidbody,
+    aabb::ContactFlags,
+    vector2::Vector2,
+};
+
+// side-view character movement on top of a dynamic `Rigidbody`, an `AABB` and
+// `Gravity`. needs `ContactFlags` to know when it's standing on something
+#[derive(Copy, Clone)]
+pub struct PlatformerController {
+    pub move_speed: f32,
+    pub jump_speed: f32,
+    // vertical speed is multiplied by this when jump is released on the way up
+    pub jump_cut: f32,
+    // how long after walking off a ledge a jump is still allowed
+    pub coyote_time: f32,
+    // how long a jump pressed just before landing is remembered
+    pub jump_buffer: f32,
+    // steepest slope, in degrees, that counts as ground and can be stood on
+    pub max_slope: f32,
+    coyote_timer: f32,
+    jump_buffer_timer: f32,
+    jump_held: bool,
+    jumping: bool,
+}
+
+impl PlatformerController {
+    // the smallest upward component a surface normal can have and still be walkable
+    pub fn min_ground_normal(&self) -> f32 {
+        self.max_slope.to_radians().cos()
+    }
+}
+
+impl Default for PlatformerController {
+    fn default() -> Self {
+        Self {
+          move_speed: 320.0,
+          jump_speed: 620.0,
+          jump_cut: 0.5,
+          coyote_time: 0.1,
+          jump_buffer: 0.1,
+          max_slope: 50.0,
+          coyote_timer: 0.0,
+          jump_buffer_timer: 0.0,
+          jump_held: false,
+          jumping: false,
+        }    
+    }  
+}
+
+impl Component for PlatformerController {}
+
+#[derive(Default)]
+pub struct PlatformerSystem {}
+
+impl System for PlatformerSystem {
+    fn update(&mut self, ctx: &mut Context, _: &EntitySystem, component_manager: &mut ComponentManager, resources: &mut Resources) {
+        let delta_time = resources.get::<Time>().map_or(0.0, |t| t.delta);
+
+        let mut x_axis = 0.0;
+
+        let pressed_left = keyboard::is_key_pressed(ctx, KeyCode::A) || keyboard::is_key_pressed(ctx, KeyCode::Left);
+        let pressed_right = keyboard::is_key_pressed(ctx, KeyCode::D) || keyboard::is_key_pressed(ctx, KeyCode::Right);
+        let pressed_jump = keyboard::is_key_pressed(ctx, KeyCode::Space) ||
+            keyboard::is_key_pressed(ctx, KeyCode::W) ||
+            keyboard::is_key_pressed(ctx, KeyCode::Up);
+
+        if pressed_left {
+            x_axis += -1.0;
+        }
+        if pressed_right {
+            x_axis += 1.0;
+        }
+
+        for (_, (rigidbody, controller, contacts)) in component_manager.query_mut::<(&mut Rigidbody, &mut PlatformerController, &ContactFlags)>() {
+            let grounded = contacts.grounded && -contacts.ground_normal.y >= controller.min_ground_normal();
+
+            if grounded {
+                controller.coyote_timer = controller.coyote_time;
+            }
+            else {
+                controller.coyote_timer -= delta_time;
+            }
+
+            if pressed_jump && !controller.jump_held {
+                controller.jump_buffer_timer = controller.jump_buffer;
+            }
+            else {
+                controller.jump_buffer_timer -= delta_time;
+            }
+            controller.jump_held = pressed_jump;
+
+            let target = x_axis * controller.move_speed;
+
+            if grounded && !controller.jumping {
+                // walk along the ground so slopes are followed instead of launched off
+                let n = contacts.ground_normal;
+                let tangent = Vector2::new(-n.y, n.x).normalize();
+                rigidbody.vel = tangent * target;
+            }
+            else {
+                rigidbody.vel.x = target;
+            }
+
+            if controller.jump_buffer_timer > 0.0 && controller.coyote_timer > 0.0 {
+                rigidbody.vel.y = -controller.jump_speed;
+                controller.jump_buffer_timer = 0.0;
+                controller.coyote_timer = 0.0;
+                controller.jumping = true;
+            }
+
+            if controller.jumping {
+                if rigidbody.vel.y >= 0.0 {
+                    controller.jumping = false;
+                }
+                else if !pressed_jump {
+                    rigidbody.vel.y *= controller.jump_cut;
+                    controller.jumping = false;
+                }
+            }
+
+            if contacts.ceiling && rigidbody.vel.y < 0.0 {
+                rigidbody.vel.y = 0.0;
+                controller.jumping = false;
+            }
+        }
+    }
+
+    fn draw(&self, _: &mut Context, _: &EntitySystem, _: &ComponentManager, _: &Resources) -> GameResult {
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+use std::{
+    any::TypeId,
+    marker::PhantomData,
+};
+
+use crate::component::{Component, ComponentList, ComponentManager};
+
+/// a single term of a query: `&T`, `&mut T` or `Option<&T>` / `Option<&mut T>`,
+/// or a tuple of terms. queries hand out references into several component lists
+/// at once, so the lists are resolved to raw pointers up front and
+/// `ComponentManager::query_mut` rejects queries that alias the same list mutably
+///
+/// # Safety
+/// `access` has to list every component the implementation touches, with `true`
+/// for the ones it hands out mutably, since that's all `check_access` goes by
+pub unsafe trait Fetch<'a> {
+    type Item;
+    type State: Copy;
+
+    fn access(access: &mut Vec<(TypeId, bool)>);
+
+    /// returns None when a required component has never been registered
+    ///
+    /// # Safety
+    /// `component_manager` has to point to a `ComponentManager` that outlives `'a`
+    unsafe fn init(component_manager: *mut ComponentManager) -> Option<Self::State>;
+
+    /// the dense entity array of the smallest required list, used to drive iteration
+    ///
+    /// # Safety
+    /// `state` has to come from `init` on a `ComponentManager` that's still alive
+    unsafe fn dense(state: Self::State) -> Option<&'a [usize]>;
+
+    /// the item for `entity`, or None when it lacks a required component
+    ///
+    /// # Safety
+    /// `state` has to come from `init` on a `ComponentManager` that's still alive, and
+    /// no two items handed out at the same time may alias a component mutably
+    unsafe fn fetch(state: Self::State, entity: usize) -> Option<Self::Item>;
+}
+
+/// marker for queries that can run against `&ComponentManager`
+///
+/// # Safety
+/// only for fetches that never hand out a mutable reference
+pub unsafe trait ReadOnlyFetch {}
+
+unsafe impl<'a, T: Component + 'static> Fetch<'a> for &'a T {
+    type Item = &'a T;
+    type State = *const ComponentList<T>;
+
+    fn access(access: &mut Vec<(TypeId, bool)>) {
+        access.push((TypeId::of::<T>(), false));
+    }
+
+    unsafe fn init(component_manager: *mut ComponentManager) -> Option<Self::State> {
+        (*component_manager).get_components::<T>().map(|c| c as *const ComponentList<T>)
+    }
+
+    unsafe fn dense(state: Self::State) -> Option<&'a [usize]> {
+        Some((*state).entities())
+    }
+
+    unsafe fn fetch(state: Self::State, entity: usize) -> Option<Self::Item> {
+        (*state).get_entity_component(entity)
+    }
+}
+
+unsafe impl<T: Component + 'static> ReadOnlyFetch for &T {}
+
+unsafe impl<'a, T: Component + 'static> Fetch<'a> for &'a mut T {
+    type Item = &'a mut T;
+    type State = *mut ComponentList<T>;
+
+    fn access(access: &mut Vec<(TypeId, bool)>) {
+        access.push((TypeId::of::<T>(), true));
+    }
+
+    unsafe fn init(component_manager: *mut ComponentManager) -> Option<Self::State> {
+        (*component_manager).get_components_mut::<T>().map(|c| c as *mut ComponentList<T>)
+    }
+
+    unsafe fn dense(state: Self::State) -> Option<&'a [usize]> {
+        Some((*state).entities())
+    }
+
+    unsafe fn fetch(state: Self::State, entity: usize) -> Option<Self::Item> {
+        (*state).get_entity_component_mut(entity)
+    }
+}
+
+unsafe impl<'a, T: Component + 'static> Fetch<'a> for Option<&'a T> {
+    type Item = Option<&'a T>;
+    type State = Option<*const ComponentList<T>>;
+
+    fn access(access: &mut Vec<(TypeId, bool)>) {
+        access.push((TypeId::of::<T>(), false));
+    }
+
+    unsafe fn init(component_manager: *mut ComponentManager) -> Option<Self::State> {
+        Some(<&'a T as Fetch<'a>>::init(component_manager))
+    }
+
+    unsafe fn dense(_: Self::State) -> Option<&'a [usize]> {
+        None
+    }
+
+    unsafe fn fetch(state: Self::State, entity: usize) -> Option<Self::Item> {
+        Some(state.and_then(|s| <&'a T as Fetch<'a>>::fetch(s, entity)))
+    }
+}
+
+unsafe impl<T: Component + 'static> ReadOnlyFetch for Option<&T> {}
+
+unsafe impl<'a, T: Component + 'static> Fetch<'a> for Option<&'a mut T> {
+    type Item = Option<&'a mut T>;
+    type State = Option<*mut ComponentList<T>>;
+
+    fn access(access: &mut Vec<(TypeId, bool)>) {
+        access.push((TypeId::of::<T>(), true));
+    }
+
+    unsafe fn init(component_manager: *mut ComponentManager) -> Option<Self::State> {
+        Some(<&'a mut T as Fetch<'a>>::init(component_manager))
+    }
+
+    unsafe fn dense(_: Self::State) -> Option<&'a [usize]> {
+        None
+    }
+
+    unsafe fn fetch(state: Self::State, entity: usize) -> Option<Self::Item> {
+        Some(state.and_then(|s| <&'a mut T as Fetch<'a>>::fetch(s, entity)))
+    }
+}
+
+macro_rules! impl_fetch_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        unsafe impl<'a, $($name: Fetch<'a>),+> Fetch<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+            type State = ($($name::State,)+);
+
+            fn access(access: &mut Vec<(TypeId, bool)>) {
+                $($name::access(access);)+
+            }
+
+            unsafe fn init(component_manager: *mut ComponentManager) -> Option<Self::State> {
+                Some(($($name::init(component_manager)?,)+))
+            }
+
+            unsafe fn dense(state: Self::State) -> Option<&'a [usize]> {
+                let ($($name,)+) = state;
+                let mut smallest: Option<&'a [usize]> = None;
+                $(
+                    if let Some(d) = $name::dense($name) {
+                        if smallest.map_or(true, |s| d.len() < s.len()) {
+                            smallest = Some(d);
+                        }
+                    }
+                )+
+                smallest
+            }
+
+            unsafe fn fetch(state: Self::State, entity: usize) -> Option<Self::Item> {
+                let ($($name,)+) = state;
+                Some(($($name::fetch($name, entity)?,)+))
+            }
+        }
+
+        unsafe impl<$($name: ReadOnlyFetch),+> ReadOnlyFetch for ($($name,)+) {}
+    };
+}
+
+impl_fetch_tuple!(A);
+impl_fetch_tuple!(A, B);
+impl_fetch_tuple!(A, B, C);
+impl_fetch_tuple!(A, B, C, D);
+impl_fetch_tuple!(A, B, C, D, E);
+impl_fetch_tuple!(A, B, C, D, E, F);
+impl_fetch_tuple!(A, B, C, D, E, F, G);
+impl_fetch_tuple!(A, B, C, D, E, F, G, H);
+
+pub struct QueryIter<'a, Q: Fetch<'a>> {
+    state: Option<Q::State>,
+    entities: &'a [usize],
+    index: usize,
+    marker: PhantomData<&'a mut ComponentManager>,
+}
+
+impl<'a, Q: Fetch<'a>> QueryIter<'a, Q> {
+    /// # Safety
+    /// the pointer has to be valid for 'a and, for mutable queries, come from a
+    /// unique borrow with no list aliased
+    pub(crate) unsafe fn new(component_manager: *mut ComponentManager) -> Self {
+        let state = Q::init(component_manager);
+        let entities = state.and_then(|s| Q::dense(s)).unwrap_or(&[]);
+
+        Self {
+            state,
+            entities,
+            index: 0,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, Q: Fetch<'a>> Iterator for QueryIter<'a, Q> {
+    type Item = (usize, Q::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let state = self.state?;
+
+        while self.index < self.entities.len() {
+            let entity = self.entities[self.index];
+            self.index += 1;
+
+            // every entity appears at most once in a dense array, so the
+            // references handed out on different iterations never overlap
+            if let Some(item) = unsafe { Q::fetch(state, entity) } {
+                return Some((entity, item));
+            }
+        }
+
+        None
+    }
+}
+
+pub fn check_access<'a, Q: Fetch<'a>>() {
+    let mut access = Vec::new();
+    Q::access(&mut access);
+
+    for (i, (type_a, mut_a)) in access.iter().enumerate() {
+        for (type_b, mut_b) in &access[i + 1..] {
+            if type_a == type_b && (*mut_a || *mut_b) {
+                panic!("query accesses the same component mutably more than once");
+            }
+        }
+    }
+}
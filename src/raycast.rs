@@ -0,0 +1,292 @@
+use crate::{
+    aabb::AABB,
+    broadphase::SpatialHash,
+    collider::{Collider, Shape},
+    component::ComponentManager,
+    entity::{Entity, EntitySystem},
+    resource::Resources,
+    vector2::Vector2,
+};
+
+#[allow(dead_code)]
+#[derive(Copy, Clone)]
+pub struct RaycastHit {
+    pub entity: Entity,
+    pub point: Vector2,
+    pub normal: Vector2,
+    pub distance: f32,
+}
+
+// first non-trigger collider along the ray whose layer is in `mask`. colliders the
+// ray starts inside of are skipped, so casting from within your own body is fine.
+// positions are as of the last `CollisionSystem` step, whose broadphase grid is
+// used to find what's near the ray, so nothing is hit before the first step
+#[allow(dead_code)]
+pub fn raycast(entities: &EntitySystem, component_manager: &ComponentManager, resources: &Resources, origin: Vector2, dir: Vector2, max_dist: f32, mask: u32) -> Option<RaycastHit> {
+    let dir = dir.normalize();
+    let bounds = AABB {
+        x: origin.x,
+        y: origin.y,
+        w: 0.0,
+        h: 0.0,
+        ..AABB::default()
+    };
+
+    let aabbs = component_manager.get_components::<AABB>()?;
+    let shapes = component_manager.get_components::<Collider>();
+
+    let mut candidates = Vec::new();
+    resources.get::<SpatialHash>()?.query(&bounds.merge(bounds.translate(dir * max_dist)), &mut candidates);
+
+    let mut best: Option<RaycastHit> = None;
+    let mut scratch = Shape::default();
+
+    for index in candidates {
+        let Some(aabb) = aabbs.get_entity_component(index) else {
+            continue;
+        };
+        if aabb.is_trigger || aabb.layer & mask == 0 {
+            continue;
+        }
+        let Some(entity) = entities.entity(index) else {
+            continue;
+        };
+
+        // cheap rejection against the bounds before the exact shape test
+        if bounds.sweep(dir * max_dist, *aabb).is_none() && !aabb.check(bounds) {
+            continue;
+        }
+
+        let center = Vector2::new(aabb.x + aabb.w * 0.5, aabb.y + aabb.h * 0.5);
+
+        scratch.set(shapes.and_then(|s| s.get_entity_component(index)), aabb, center);
+        if let Some((distance, normal)) = scratch.raycast(origin, dir, max_dist) {
+            if best.is_none_or(|b| distance < b.distance) {
+                best = Some(RaycastHit {
+                    entity,
+                    point: origin + dir * distance,
+                    normal,
+                    distance,
+                });
+            }
+        }
+    }
+
+    best
+}
+
+// first collider hit by `aabb` moving by `dir`, filtered by the box's own layer and
+// mask. other shapes are cast against as their bounding boxes. `point` is the
+// center of the box at the time of impact
+#[allow(dead_code)]
+pub fn shape_cast(entities: &EntitySystem, component_manager: &ComponentManager, resources: &Resources, aabb: AABB, dir: Vector2) -> Option<RaycastHit> {
+    let length = dir.length();
+
+    let aabbs = component_manager.get_components::<AABB>()?;
+
+    let mut candidates = Vec::new();
+    resources.get::<SpatialHash>()?.query(&aabb.merge(aabb.translate(dir)), &mut candidates);
+
+    let mut best: Option<RaycastHit> = None;
+
+    for index in candidates {
+        let Some(b) = aabbs.get_entity_component(index) else {
+            continue;
+        };
+        if b.is_trigger || !aabb.collides_with(b) {
+            continue;
+        }
+        let Some(entity) = entities.entity(index) else {
+            continue;
+        };
+
+        if let Some((t, normal)) = aabb.sweep(dir, *b) {
+            let distance = t * length;
+            if best.is_none_or(|h| distance < h.distance) {
+                best = Some(RaycastHit {
+                    entity,
+                    point: Vector2::new(aabb.x + aabb.w * 0.5, aabb.y + aabb.h * 0.5) + dir * t,
+                    normal,
+                    distance,
+                });
+            }
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        aabb::CollisionSystem,
+        rigidbody::Rigidbody,
+        transform::Transform,
+    };
+
+    struct World {
+        entities: EntitySystem,
+        component_manager: ComponentManager,
+        resources: Resources,
+    }
+
+    impl World {
+        fn new() -> Self {
+            Self {
+                entities: EntitySystem::new(),
+                component_manager: ComponentManager::new(),
+                resources: Resources::new(),
+            }
+        }
+
+        // a collider without a rigidbody centered on `pos`
+        fn add(&mut self, pos: Vector2, aabb: AABB, shape: Option<Collider>) -> Entity {
+            let entity = self.entities.spawn((Transform::new(pos), aabb), &mut self.component_manager);
+            if let Some(shape) = shape {
+                self.entities.insert_component(entity, shape, &mut self.component_manager);
+            }
+            entity
+        }
+
+        // positions every box and files it in the grid, like a frame would
+        fn step(&mut self) {
+            CollisionSystem::default().step(&self.entities, &mut self.component_manager, &mut self.resources);
+        }
+
+        fn raycast(&self, origin: Vector2, dir: Vector2, max_dist: f32, mask: u32) -> Option<RaycastHit> {
+            raycast(&self.entities, &self.component_manager, &self.resources, origin, dir, max_dist, mask)
+        }
+    }
+
+    fn square(size: f32) -> AABB {
+        AABB { w: size, h: size, ..AABB::default() }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn hits_the_nearest_box() {
+        let mut world = World::new();
+        let far = world.add(Vector2::new(200.0, 0.0), square(20.0), None);
+        let near = world.add(Vector2::new(100.0, 0.0), square(20.0), None);
+        world.step();
+
+        let hit = world.raycast(Vector2::default(), Vector2::new(1.0, 0.0), 500.0, u32::MAX).expect("both boxes are in the way");
+        assert_eq!(hit.entity, near);
+        assert!(close(hit.distance, 90.0));
+        assert!(close(hit.point.x, 90.0));
+        assert_eq!((hit.normal.x, hit.normal.y), (-1.0, 0.0));
+        assert_ne!(hit.entity, far);
+    }
+
+    #[test]
+    fn hits_a_circle_off_center() {
+        let mut world = World::new();
+        let circle = world.add(Vector2::new(100.0, 0.0), square(1.0), Some(Collider::Circle { radius: 10.0 }));
+        world.step();
+
+        let hit = world.raycast(Vector2::new(0.0, 5.0), Vector2::new(1.0, 0.0), 500.0, u32::MAX).expect("the circle is in the way");
+        assert_eq!(hit.entity, circle);
+        assert!(close(hit.distance, 100.0 - 75.0_f32.sqrt()));
+        assert!(hit.normal.x < 0.0 && hit.normal.y > 0.0);
+
+        // passing through the circle's bounding box but not the circle itself
+        assert!(world.raycast(Vector2::new(0.0, 9.9), Vector2::new(1.0, 0.0), 500.0, u32::MAX)
+            .is_none_or(|h| h.distance > 98.0));
+    }
+
+    #[test]
+    fn hits_a_polygon_on_its_slanted_edge() {
+        let mut world = World::new();
+        let diamond = Collider::Polygon {
+            points: vec![
+                Vector2::new(0.0, -10.0),
+                Vector2::new(10.0, 0.0),
+                Vector2::new(0.0, 10.0),
+                Vector2::new(-10.0, 0.0),
+            ],
+        };
+        let polygon = world.add(Vector2::new(100.0, 0.0), square(1.0), Some(diamond));
+        world.step();
+
+        let hit = world.raycast(Vector2::new(0.0, 5.0), Vector2::new(1.0, 0.0), 500.0, u32::MAX).expect("the polygon is in the way");
+        assert_eq!(hit.entity, polygon);
+        assert!(close(hit.distance, 95.0));
+        assert!(close(hit.normal.x, -std::f32::consts::FRAC_1_SQRT_2));
+        assert!(close(hit.normal.y, std::f32::consts::FRAC_1_SQRT_2));
+    }
+
+    #[test]
+    fn skips_layers_outside_the_mask_and_triggers() {
+        let mut world = World::new();
+        world.add(Vector2::new(50.0, 0.0), AABB { is_trigger: true, ..square(20.0) }, None);
+        let wall = world.add(Vector2::new(100.0, 0.0), AABB { layer: 2, ..square(20.0) }, None);
+        world.step();
+
+        assert!(world.raycast(Vector2::default(), Vector2::new(1.0, 0.0), 500.0, 1).is_none());
+        assert_eq!(world.raycast(Vector2::default(), Vector2::new(1.0, 0.0), 500.0, 2).map(|h| h.entity), Some(wall));
+    }
+
+    #[test]
+    fn skips_the_shape_the_ray_starts_in() {
+        let mut world = World::new();
+        world.add(Vector2::default(), square(20.0), None);
+        world.add(Vector2::new(30.0, 0.0), square(20.0), Some(Collider::Circle { radius: 40.0 }));
+        let wall = world.add(Vector2::new(100.0, 0.0), square(20.0), None);
+        world.step();
+
+        let hit = world.raycast(Vector2::default(), Vector2::new(1.0, 0.0), 500.0, u32::MAX);
+        assert_eq!(hit.map(|h| h.entity), Some(wall));
+    }
+
+    #[test]
+    fn stops_at_max_dist() {
+        let mut world = World::new();
+        world.add(Vector2::new(100.0, 0.0), square(20.0), None);
+        world.step();
+
+        assert!(world.raycast(Vector2::default(), Vector2::new(1.0, 0.0), 80.0, u32::MAX).is_none());
+        assert!(world.raycast(Vector2::default(), Vector2::new(-1.0, 0.0), 500.0, u32::MAX).is_none());
+    }
+
+    #[test]
+    fn finds_bodies_where_the_last_step_left_them() {
+        let mut world = World::new();
+        world.add(Vector2::new(100.0, 0.0), AABB { w: 1.0, h: 100.0, ..AABB::default() }, None);
+
+        // filed for the step way past the wall, but swept back to it
+        let mut transform = Transform::new(Vector2::new(0.0, 0.0));
+        transform.set_pos(Vector2::new(300.0, 0.0));
+        let body = world.entities.spawn((transform, Rigidbody::default(), square(20.0)), &mut world.component_manager);
+        world.step();
+
+        let hit = world.raycast(Vector2::new(89.5, -100.0), Vector2::new(0.0, 1.0), 500.0, u32::MAX);
+        assert_eq!(hit.map(|h| h.entity), Some(body));
+        assert!(world.raycast(Vector2::new(300.0, -100.0), Vector2::new(0.0, 1.0), 500.0, u32::MAX).is_none());
+    }
+
+    #[test]
+    fn shape_cast_stops_at_the_first_box() {
+        let mut world = World::new();
+        let wall = world.add(Vector2::new(100.0, 0.0), square(20.0), None);
+        world.step();
+
+        let hit = shape_cast(&world.entities, &world.component_manager, &world.resources, square(10.0).centered_at(Vector2::default()), Vector2::new(200.0, 0.0))
+            .expect("the wall is in the way");
+        assert_eq!(hit.entity, wall);
+        assert!(close(hit.distance, 85.0));
+        assert!(close(hit.point.x, 85.0));
+    }
+
+    #[test]
+    fn nothing_is_hit_before_the_first_step() {
+        let mut world = World::new();
+        world.add(Vector2::new(100.0, 0.0), square(20.0), None);
+
+        assert!(world.raycast(Vector2::default(), Vector2::new(1.0, 0.0), 500.0, u32::MAX).is_none());
+    }
+}
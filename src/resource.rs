@@ -0,0 +1,73 @@
+use std::{
+    collections::HashMap,
+    any::{
+        TypeId,
+        Any
+    },
+};
+
+use rand::rngs::StdRng;
+
+// global singletons that don't belong to any entity, e.g. time, world bounds or the rng.
+// there's at most one value of each type
+pub trait Resource {}
+
+impl Resource for StdRng {}
+
+pub struct Resources {
+    pub resources: HashMap<TypeId, Box<dyn Any>>
+}
+
+#[allow(dead_code)]
+impl Resources {
+    pub fn new() -> Self {
+        Self {
+            resources: HashMap::new()
+        }
+    }
+
+    // replaces and returns whatever value of the same type was there before
+    pub fn insert<T: Resource + 'static>(&mut self, resource: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), Box::new(resource))
+            .and_then(|old| old.downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn remove<T: Resource + 'static>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|old| old.downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn get<T: Resource + 'static>(&self) -> Option<&T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|r| r.downcast_ref::<T>())
+    }
+
+    pub fn get_mut<T: Resource + 'static>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|r| r.downcast_mut::<T>())
+    }
+
+    pub fn get_or_insert_default<T: Resource + Default + 'static>(&mut self) -> &mut T {
+        self.resources
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()))
+            .downcast_mut::<T>()
+            .expect("resource stored under the wrong type")
+    }
+
+    pub fn contains<T: Resource + 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+}
+
+impl Default for Resources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
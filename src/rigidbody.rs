@@ -1,20 +1,67 @@
 use crate::vector2::Vector2;
 use crate::component::Component;
 
+#[allow(dead_code)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BodyType {
+    // never moves
+    Static,
+    // moved by its velocity only, ignores forces and gravity and pushes dynamic bodies
+    Kinematic,
+    // fully simulated
+    Dynamic,
+}
+
 #[derive(Copy, Clone)]
 pub struct Rigidbody {
+    pub body_type: BodyType,
     pub vel: Vector2,
-    pub accel: Vector2
+    pub accel: Vector2,
+    pub mass: f32,
+    pub inv_mass: f32,
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+#[allow(dead_code)]
+impl Rigidbody {
+    // a mass of zero (or less) makes the body immovable by collisions
+    pub fn set_mass(&mut self, mass: f32) -> &mut Self {
+      self.mass = mass;
+      self.inv_mass = if mass > 0.0 { 1.0 / mass } else { 0.0 };
+      self
+    }
+
+    // what collisions actually use: only dynamic bodies can be pushed around
+    pub fn effective_inv_mass(&self) -> f32 {
+      if self.body_type == BodyType::Dynamic { self.inv_mass } else { 0.0 }
+    }
+
+    // accumulated into `accel` and cleared once the physics step has integrated it
+    pub fn apply_force(&mut self, force: Vector2) -> &mut Self {
+      self.accel = self.accel + force * self.effective_inv_mass();
+      self
+    }
+
+    pub fn apply_impulse(&mut self, impulse: Vector2) -> &mut Self {
+      self.vel = self.vel + impulse * self.effective_inv_mass();
+      self
+    }
 }
 
 impl Default for Rigidbody {
     fn default() -> Self {
       Self {
+        body_type: BodyType::Dynamic,
         vel: Vector2::default(),
-        accel: Vector2::default()
+        accel: Vector2::default(),
+        mass: 1.0,
+        inv_mass: 1.0,
+        restitution: 0.0,
+        friction: 0.3,
       }    
     }  
   }
 
 impl Component for Rigidbody {}
-  
\ No newline at end of file
+  
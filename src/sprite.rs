@@ -1,8 +1,10 @@
 use ggez::{graphics::{self, Color}, Context, mint::Vector2, GameResult};
 
 use crate::{
-    system::System,
+    system::{System, Time},
+    resource::Resources,
     component::{ComponentManager, Component}, transform::Transform,
+    entity::EntitySystem,
 };
 
 #[derive(Copy, Clone)]
@@ -33,40 +35,35 @@ impl Default for RenderSystem {
 }
 
 impl System for RenderSystem {
-    fn update(&mut self, _: &mut Context, _: usize, _: &mut ComponentManager) {
+    fn update(&mut self, _: &mut Context, _: &EntitySystem, _: &mut ComponentManager, _: &mut Resources) {
     }
 
-    fn draw(&self, ctx: &mut Context, entities: usize, component_manager: &ComponentManager) -> GameResult {
-        for entity in 0..entities {
-            let has_sprite_component = component_manager.entity_has_component::<Sprite>(entity);
-            let has_transform_component = component_manager.entity_has_component::<Transform>(entity);
+    fn draw(&self, ctx: &mut Context, _: &EntitySystem, component_manager: &ComponentManager, resources: &Resources) -> GameResult {
+        let alpha = resources.get::<Time>().map_or(1.0, |t| t.alpha);
 
-            if has_sprite_component && has_transform_component {
-                let sprite = component_manager.get_components::<Sprite>().unwrap().get_entity_component(entity).unwrap();
+        for (_, (sprite, transform)) in component_manager.query::<(&Sprite, &Transform)>() {
+            let rect = graphics::Rect::new(
+                0.0,
+                0.0,
+                sprite.w as f32,
+                sprite.h as f32
+            );
+            let rect_mesh = graphics::Mesh::new_rectangle(
+                ctx,
+                graphics::DrawMode::fill(),
+                rect,
+                sprite.color
+            )?;
 
-                let rect = graphics::Rect::new(
-                    0.0,
-                    0.0,
-                    sprite.w as f32,
-                    sprite.h as f32
-                );
-                let rect_mesh = graphics::Mesh::new_rectangle(
-                    ctx,
-                    graphics::DrawMode::fill(),
-                    rect,
-                    sprite.color
-                )?;
+            let player_position = crate::vector2::Vector2::default().lerp(transform.prev_pos, transform.pos, alpha);
+            let pos = Vector2 {
+                x: player_position.x - sprite.w as f32 * 0.5,
+                y: player_position.y - sprite.h as f32 * 0.5
+            };
+            let draw_params = graphics::DrawParam::new()
+                .dest(pos);
 
-                let player_position = component_manager.get_components::<Transform>().unwrap().get_entity_component(entity).unwrap().pos;
-                let pos = Vector2 {
-                    x: player_position.x - sprite.w as f32 * 0.5,
-                    y: player_position.y - sprite.h as f32 * 0.5
-                };
-                let draw_params = graphics::DrawParam::new()
-                    .dest(pos);
-
-                graphics::draw(ctx, &rect_mesh, draw_params)?;
-            }
+            graphics::draw(ctx, &rect_mesh, draw_params)?;
         }
 
         Ok(())
@@ -1,24 +1,117 @@
-use std::{
-  collections::HashMap,
-  any::{
-      TypeId,
-  }
+use std::any::{
+  TypeId,
+  type_name,
 };
-use ggez::{Context, GameResult};
+use ggez::{Context, GameError, GameResult, timer};
 
 use crate::{
   entity::EntitySystem,
-  component::ComponentManager
+  component::ComponentManager,
+  resource::{Resource, Resources},
+  events::{Events, NO_READER},
+  commands::Commands,
+  transform::Transform,
 };
 
 pub trait System {
-  fn update(&mut self, ctx: &mut Context, entities: usize, component_manager: &mut ComponentManager);
-  fn draw(&self, ctx: &mut Context, entities: usize, component_manager: &ComponentManager) -> GameResult;
+  fn update(&mut self, ctx: &mut Context, entities: &EntitySystem, component_manager: &mut ComponentManager, resources: &mut Resources);
+  fn draw(&self, ctx: &mut Context, entities: &EntitySystem, component_manager: &ComponentManager, resources: &Resources) -> GameResult;
+}
+
+// kept up to date by the `SystemManager`. `delta` is the fixed step while the
+// simulation stages run and the frame time during the render stage
+#[derive(Copy, Clone, Default)]
+pub struct Time {
+  pub delta: f32,
+  pub elapsed: f32,
+  // how far the leftover frame time is into the next step, for render interpolation
+  pub alpha: f32,
+}
+
+impl Resource for Time {}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Stage {
+  PreUpdate,
+  Update,
+  PostUpdate,
+  Render,
+}
+
+const STAGES: [Stage; 4] = [Stage::PreUpdate, Stage::Update, Stage::PostUpdate, Stage::Render];
+
+#[derive(Clone)]
+pub struct SystemConfig {
+  pub stage: Stage,
+  pub before: Vec<TypeId>,
+  pub after: Vec<TypeId>,
+}
+
+#[allow(dead_code)]
+impl SystemConfig {
+  pub fn new(stage: Stage) -> Self {
+    Self {
+      stage,
+      before: Vec::new(),
+      after: Vec::new(),
+    }
+  }
+
+  pub fn before<T: System + 'static>(mut self) -> Self {
+    self.before.push(TypeId::of::<T>());
+    self
+  }
+
+  pub fn after<T: System + 'static>(mut self) -> Self {
+    self.after.push(TypeId::of::<T>());
+    self
+  }
+}
+
+impl Default for SystemConfig {
+  fn default() -> Self {
+    Self::new(Stage::Update)
+  }
+}
+
+// runs the simulation stages at a fixed rate regardless of frame rate. `alpha`
+// is how far the leftover time is into the next step, for render interpolation
+pub struct FixedTimestep {
+  pub step: f32,
+  pub max_steps: u32,
+  accumulator: f32,
+  alpha: f32,
+}
+
+impl FixedTimestep {
+  pub fn new(hz: f32, max_steps: u32) -> Self {
+    Self {
+      step: 1.0 / hz,
+      max_steps,
+      accumulator: 0.0,
+      alpha: 0.0,
+    }
+  }
+}
+
+impl Default for FixedTimestep {
+  fn default() -> Self {
+    Self::new(60.0, 5)
+  }
+}
+
+struct SystemEntry {
+  id: TypeId,
+  name: &'static str,
+  config: SystemConfig,
+  system: Box<dyn System>,
 }
 
 pub struct SystemManager {
   pub entity_system: EntitySystem,
-  pub systems: HashMap<TypeId, Box<dyn System>>
+  pub timestep: FixedTimestep,
+  systems: Vec<SystemEntry>,
+  order: Vec<usize>,
 }
 
 #[allow(dead_code)]
@@ -26,30 +119,281 @@ impl SystemManager {
   pub fn new() -> Self {
     Self {
       entity_system: EntitySystem::new(),
-      systems: HashMap::new(),
+      timestep: FixedTimestep::default(),
+      systems: Vec::new(),
+      order: Vec::new(),
+    }
+  }
+
+  pub fn register_system<T: System + Default + 'static>(&mut self) -> GameResult {
+    self.register_system_with::<T>(SystemConfig::default())
+  }
+
+  pub fn register_system_with<T: System + Default + 'static>(&mut self, config: SystemConfig) -> GameResult {
+    let name = type_name::<T>();
+
+    if self.systems.iter().any(|s| s.id == TypeId::of::<T>()) {
+      return Err(GameError::CustomError(format!("system {} is already registered", name)));
+    }
+
+    self.systems.push(SystemEntry {
+      id: TypeId::of::<T>(),
+      name,
+      config,
+      system: Box::new(T::default()),
+    });
+
+    match self.schedule() {
+      Ok(order) => {
+        self.order = order;
+        Ok(())
+      },
+      Err(e) => {
+        self.systems.pop();
+        Err(e)
+      }
+    }
+  }
+
+  pub fn get_system<T: System + 'static>(&self) -> Option<&dyn System> {
+    self.systems
+      .iter()
+      .find(|s| s.id == TypeId::of::<T>())
+      .map(|s| s.system.as_ref())
+  }
+
+  // whether `a` has to run before `b` according to either system's constraints
+  fn runs_before(a: &SystemEntry, b: &SystemEntry) -> bool {
+    a.config.before.contains(&b.id) || b.config.after.contains(&a.id)
+  }
+
+  // orders systems by stage, then topologically within each stage. ties are
+  // broken by registration order so the schedule is the same on every run
+  fn schedule(&self) -> GameResult<Vec<usize>> {
+    for a in &self.systems {
+      for b in &self.systems {
+        if Self::runs_before(a, b) && a.config.stage > b.config.stage {
+          return Err(GameError::CustomError(format!(
+            "system {} ({:?}) can't run before {} ({:?})",
+            a.name, a.config.stage, b.name, b.config.stage
+          )));
+        }
+      }
+    }
+
+    let mut order = Vec::with_capacity(self.systems.len());
+
+    for stage in STAGES {
+      let mut pending: Vec<usize> = (0..self.systems.len())
+        .filter(|i| self.systems[*i].config.stage == stage)
+        .collect();
+
+      while !pending.is_empty() {
+        let ready = pending.iter().position(|i| {
+          pending.iter().all(|j| !Self::runs_before(&self.systems[*j], &self.systems[*i]))
+        });
+
+        match ready {
+          Some(p) => order.push(pending.remove(p)),
+          None => {
+            let names: Vec<&str> = pending.iter().map(|i| self.systems[*i].name).collect();
+            return Err(GameError::CustomError(format!(
+              "system ordering cycle in {:?} stage between {}",
+              stage, names.join(", ")
+            )));
+          }
+        }
+      }
     }
+
+    Ok(order)
   }
 
-  pub fn register_system<T: 'static>(&mut self) where T: System + Default {
-    let system = T::default();
-    self.systems.insert(TypeId::of::<T>(), Box::new(system));
+  pub fn set_fixed_timestep(&mut self, hz: f32, max_steps: u32) {
+    self.timestep = FixedTimestep::new(hz, max_steps);
   }
 
-  pub fn get_system<T: 'static>(&self) -> Option<&Box<dyn System>> where T : System {
-    self.systems.get(&TypeId::of::<T>())
+  fn run_stages(&mut self, ctx: &mut Context, dt: f32, render: bool, component_manager: &mut ComponentManager, resources: &mut Resources) {
+    let time = resources.get_or_insert_default::<Time>();
+    time.delta = dt;
+    time.alpha = self.timestep.alpha;
+    if !render {
+      time.elapsed += dt;
+      Self::snapshot_transforms(component_manager);
+    }
+
+    for stage in STAGES {
+      if (stage == Stage::Render) != render {
+        continue;
+      }
+
+      for i in &self.order {
+        let entry = &mut self.systems[*i];
+        if entry.config.stage == stage {
+          // systems are never unregistered, so their index doubles as their event reader id
+          resources.get_or_insert_default::<Events>().set_reader(*i);
+          entry.system.update(ctx, &self.entity_system, component_manager, resources);
+        }
+      }
+
+      // sync point: structural changes recorded during the stage become visible to the next one
+      self.apply_commands(component_manager, resources);
+    }
+
+    let events = resources.get_or_insert_default::<Events>();
+    events.set_reader(NO_READER);
+    // a frame can run any number of steps, so events age per step rather than per frame
+    if !render {
+      events.update();
+    }
   }
 
-  pub fn update(&mut self, ctx: &mut Context, component_manager: &mut ComponentManager) {
-    for (_, system) in &mut self.systems {
-      system.update(ctx, self.entity_system.entities, component_manager);
+  // every transform starts the step where it ended the last one, whichever system
+  // ends up moving it, so interpolation and sweeps never see a stale `prev_pos`
+  fn snapshot_transforms(component_manager: &mut ComponentManager) {
+    if let Some(transforms) = component_manager.get_components_mut::<Transform>() {
+      for transform in transforms.iter_mut() {
+        transform.prev_pos = transform.pos;
+      }
     }
   }
 
-  pub fn draw(&self, ctx: &mut Context, component_manager: &ComponentManager) -> GameResult {
-    for (_, system) in &self.systems {
-      system.draw(ctx, self.entity_system.entities, component_manager)?;
+  // also runs commands queued from outside of any system, e.g. by `main.rs`
+  pub fn apply_commands(&mut self, component_manager: &mut ComponentManager, resources: &mut Resources) {
+    if let Some(commands) = resources.get_mut::<Commands>() {
+      commands.apply(&mut self.entity_system, component_manager);
+    }
+  }
+
+  pub fn update(&mut self, ctx: &mut Context, component_manager: &mut ComponentManager, resources: &mut Resources) {
+    let frame_time = timer::delta(ctx).as_secs_f32();
+    let step = self.timestep.step;
+
+    self.timestep.accumulator += frame_time;
+
+    let mut steps = 0;
+    while self.timestep.accumulator >= step && steps < self.timestep.max_steps {
+      self.run_stages(ctx, step, false, component_manager, resources);
+      self.timestep.accumulator -= step;
+      steps += 1;
+    }
+
+    // drop whatever couldn't be caught up on instead of spiralling further behind
+    if self.timestep.accumulator >= step {
+      self.timestep.accumulator %= step;
+    }
+
+    self.timestep.alpha = self.timestep.accumulator / step;
+
+    self.run_stages(ctx, frame_time, true, component_manager, resources);
+  }
+
+  pub fn draw(&self, ctx: &mut Context, component_manager: &ComponentManager, resources: &Resources) -> GameResult {
+    for i in &self.order {
+      self.systems[*i].system.draw(ctx, &self.entity_system, component_manager, resources)?;
     }
 
     Ok(())
   }
 }
+
+impl Default for SystemManager {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  macro_rules! dummy_systems {
+    ($($name:ident),+) => {
+      $(
+        #[derive(Default)]
+        struct $name;
+
+        impl System for $name {
+          fn update(&mut self, _: &mut Context, _: &EntitySystem, _: &mut ComponentManager, _: &mut Resources) {}
+
+          fn draw(&self, _: &mut Context, _: &EntitySystem, _: &ComponentManager, _: &Resources) -> GameResult {
+            Ok(())
+          }
+        }
+      )+
+    };
+  }
+
+  dummy_systems!(A, B, C, D);
+
+  fn order(manager: &SystemManager) -> Vec<TypeId> {
+    manager.order.iter().map(|i| manager.systems[*i].id).collect()
+  }
+
+  #[test]
+  fn ties_keep_registration_order() {
+    let mut manager = SystemManager::new();
+    manager.register_system::<C>().unwrap();
+    manager.register_system::<A>().unwrap();
+    manager.register_system::<B>().unwrap();
+
+    assert_eq!(order(&manager), vec![TypeId::of::<C>(), TypeId::of::<A>(), TypeId::of::<B>()]);
+  }
+
+  #[test]
+  fn stages_run_in_order_regardless_of_registration() {
+    let mut manager = SystemManager::new();
+    manager.register_system_with::<A>(SystemConfig::new(Stage::PostUpdate)).unwrap();
+    manager.register_system_with::<B>(SystemConfig::new(Stage::Update)).unwrap();
+    manager.register_system_with::<C>(SystemConfig::new(Stage::PreUpdate)).unwrap();
+
+    assert_eq!(order(&manager), vec![TypeId::of::<C>(), TypeId::of::<B>(), TypeId::of::<A>()]);
+  }
+
+  #[test]
+  fn before_and_after_reorder_within_a_stage() {
+    let mut manager = SystemManager::new();
+    manager.register_system::<A>().unwrap();
+    manager.register_system::<B>().unwrap();
+    manager.register_system_with::<C>(SystemConfig::default().before::<A>()).unwrap();
+    manager.register_system_with::<D>(SystemConfig::default().after::<C>().before::<B>()).unwrap();
+
+    assert_eq!(
+      order(&manager),
+      vec![TypeId::of::<C>(), TypeId::of::<A>(), TypeId::of::<D>(), TypeId::of::<B>()]
+    );
+  }
+
+  #[test]
+  fn cycle_is_rejected_and_leaves_the_schedule_alone() {
+    let mut manager = SystemManager::new();
+    manager.register_system_with::<A>(SystemConfig::default().after::<B>()).unwrap();
+
+    assert!(manager.register_system_with::<B>(SystemConfig::default().after::<A>()).is_err());
+    assert_eq!(manager.systems.len(), 1);
+    assert_eq!(order(&manager), vec![TypeId::of::<A>()]);
+
+    // with the offending system gone it can be registered without the constraint
+    manager.register_system::<B>().unwrap();
+    assert_eq!(order(&manager), vec![TypeId::of::<B>(), TypeId::of::<A>()]);
+  }
+
+  #[test]
+  fn after_a_system_in_a_later_stage_is_rejected() {
+    let mut manager = SystemManager::new();
+    manager.register_system_with::<A>(SystemConfig::new(Stage::PostUpdate)).unwrap();
+
+    assert!(manager.register_system_with::<B>(SystemConfig::new(Stage::Update).after::<A>()).is_err());
+    assert!(manager.register_system_with::<B>(SystemConfig::new(Stage::Render).before::<A>()).is_err());
+    assert_eq!(order(&manager), vec![TypeId::of::<A>()]);
+  }
+
+  #[test]
+  fn registering_twice_is_rejected() {
+    let mut manager = SystemManager::new();
+    manager.register_system::<A>().unwrap();
+
+    assert!(manager.register_system::<A>().is_err());
+    assert_eq!(manager.systems.len(), 1);
+  }
+}
@@ -3,20 +3,40 @@ use crate::component::Component;
 
 #[derive(Copy, Clone)]
 pub struct Transform {
-  pub pos: Vector2
+  pub pos: Vector2,
+  // position at the start of the last simulation step, used to interpolate rendering
+  pub prev_pos: Vector2,
 }
 
+#[allow(dead_code)]
 impl Transform {
+  pub fn new(pos: Vector2) -> Self {
+    Self {
+      pos,
+      prev_pos: pos,
+    }
+  }
+
+  // moves through the space in between, so the move is swept by collision and
+  // interpolated when rendering
   pub fn set_pos(&mut self, new_pos: Vector2) -> &mut Self {
     self.pos = new_pos;
     self
   }
+
+  // jumps straight to `new_pos` without passing through anything on the way
+  pub fn teleport(&mut self, new_pos: Vector2) -> &mut Self {
+    self.pos = new_pos;
+    self.prev_pos = new_pos;
+    self
+  }
 }
 
 impl Default for Transform {
   fn default() -> Self {
     Self {
-      pos: Vector2::new(0.0, 0.0)
+      pos: Vector2::new(0.0, 0.0),
+      prev_pos: Vector2::new(0.0, 0.0),
     }    
   }  
 }
@@ -12,6 +12,24 @@ impl Vector2 {
     Self { x, y }
   }
 
+  pub fn dot(&self, b: Vector2) -> f32 {
+    self.x * b.x + self.y * b.y
+  }
+
+  pub fn length(&self) -> f32 {
+    self.dot(*self).sqrt()
+  }
+
+  pub fn normalize(&self) -> Self {
+    let length = self.length();
+    if length > 0.0 {
+      Self::new(self.x / length, self.y / length)
+    }
+    else {
+      Self::default()
+    }
+  }
+
   pub fn clamp(&mut self, min: Vector2, max: Vector2) -> Self {
     let x = if self.x < min.x {
       min.x
@@ -141,8 +159,19 @@ impl ops::Sub<Vector2> for Vector2 {
 
   fn sub(self, b: Self) -> Self::Output {
     Self {
-      x: self.x + b.x,
-      y: self.y + b.y
+      x: self.x - b.x,
+      y: self.y - b.y
+    }
+  }
+}
+
+impl ops::Neg for Vector2 {
+  type Output = Self;
+
+  fn neg(self) -> Self::Output {
+    Self {
+      x: -self.x,
+      y: -self.y
     }
   }
 }